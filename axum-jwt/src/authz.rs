@@ -0,0 +1,761 @@
+//! Declarative authorization.
+//!
+//! Instead of writing a filter that scans the token claims by hand, describe
+//! what the token must contain with a [requirement](Require):
+//!
+//! * [`require_scopes`] and [`require_any_scope`] read the `scope` claim,
+//!   which is a space-delimited string as defined by [RFC 8693].
+//! * [`require_roles`] and [`require_any_role`] read the `roles` claim,
+//!   which is an array of strings.
+//! * [`require_all`] and [`require_any`] combine other requirements.
+//!
+//! [RFC 8693]: https://datatracker.ietf.org/doc/html/rfc8693#section-4.2
+//!
+//! Both forms, a space-delimited string and an array of strings, are accepted
+//! in any claim. To read the values from another claim, for example,
+//! `permissions`, or from a nested one, like `realm_access.roles` in Keycloak
+//! tokens, use the [`with_claim`](Require::with_claim) method. For claim
+//! names containing dots, like the namespaced claims in Auth0 tokens, use
+//! the [`with_claim_path`](Require::with_claim_path) method.
+//!
+//! If the token doesn't satisfy the requirement, the request is rejected with
+//! [`Error::InsufficientScope`], which is rendered as `403 Forbidden` with
+//! the `insufficient_scope` error code.
+//!
+//! # Examples
+//!
+//! Apply the requirement in the [middleware](crate::layer):
+//!
+//! ```
+//! use {
+//!     axum::{Router, routing},
+//!     axum_jwt::{Decoder, authz, jsonwebtoken::DecodingKey},
+//! };
+//!
+//! let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+//!
+//! # async fn orders() {}
+//! let app = Router::new()
+//!     .route("/orders", routing::get(orders))
+//!     .layer(axum_jwt::layer(decoder).with_require(authz::require_all([
+//!         authz::require_scopes(["read:orders"]),
+//!         authz::require_any_role(["admin", "manager"]).with_claim("realm_access.roles"),
+//!     ])));
+//! # let _: Router = app;
+//! ```
+//!
+//! Or check it in a handler:
+//!
+//! ```
+//! use {
+//!     axum_jwt::{Claims, Error, authz},
+//!     serde_json::Value,
+//! };
+//!
+//! async fn orders(Claims(claims): Claims<Value>) -> Result<String, Error> {
+//!     authz::require_scopes(["read:orders"]).check(&claims)?;
+//!     Ok("Orders".to_owned())
+//! }
+//! ```
+//!
+//! The requirement can also be declared in the handler signature with
+//! the [`RequireScope`] extractor or, for any requirement, with
+//! the [`Authorized`] one.
+
+use {
+    crate::{
+        decode::Decoder,
+        error::{Error, Rejection},
+        extract::{self, Bearer, Extract, FromState},
+        verify::{self, Verifier},
+    },
+    axum_core::extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+    jsonwebtoken::TokenData,
+    serde::de::{DeserializeOwned, IgnoredAny},
+    serde_json::Value,
+    std::{any, borrow::Cow, fmt, marker::PhantomData, sync::Arc},
+};
+
+/// Requires all the scopes in the `scope` claim.
+pub fn require_scopes<I, S>(scopes: I) -> Require
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    Require::values("scope", scopes, Match::All)
+}
+
+/// Requires any of the scopes in the `scope` claim.
+pub fn require_any_scope<I, S>(scopes: I) -> Require
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    Require::values("scope", scopes, Match::Any)
+}
+
+/// Requires all the roles in the `roles` claim.
+pub fn require_roles<I, S>(roles: I) -> Require
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    Require::values("roles", roles, Match::All)
+}
+
+/// Requires any of the roles in the `roles` claim.
+pub fn require_any_role<I, S>(roles: I) -> Require
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    Require::values("roles", roles, Match::Any)
+}
+
+/// Requires all the requirements to be satisfied.
+pub fn require_all<I>(requirements: I) -> Require
+where
+    I: IntoIterator<Item = Require>,
+{
+    Require::new(Kind::All(requirements.into_iter().collect()))
+}
+
+/// Requires any of the requirements to be satisfied.
+pub fn require_any<I>(requirements: I) -> Require
+where
+    I: IntoIterator<Item = Require>,
+{
+    Require::new(Kind::Any(requirements.into_iter().collect()))
+}
+
+/// An authorization requirement.
+///
+/// To create it, use the functions of the [module](self). To apply it in
+/// the middleware, pass it to the
+/// [`with_require`](crate::layer::JwtLayer::with_require) method.
+#[derive(Clone, Debug)]
+pub struct Require(Arc<Rule>);
+
+impl Require {
+    fn new(kind: Kind) -> Self {
+        Self(Arc::new(Rule { claim: None, kind }))
+    }
+
+    fn values<I, S>(claim: &'static str, values: I, matches: Match) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::new(Kind::Values {
+            claim,
+            values: values.into_iter().map(S::into).collect(),
+            matches,
+        })
+    }
+
+    /// Sets the claim to read the values from.
+    ///
+    /// The claim is a dot-separated path to the value, so nested claims
+    /// can be read. For combined requirements, the claim is used by
+    /// the inner ones, unless they set their own claim, so the innermost
+    /// claim wins. If the method is called again, the last claim wins.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axum_jwt::authz;
+    ///
+    /// // Keycloak realm roles
+    /// let admin = authz::require_any_role(["admin"]).with_claim("realm_access.roles");
+    ///
+    /// // Auth0 permissions
+    /// let read = authz::require_scopes(["read:orders"]).with_claim("permissions");
+    ///
+    /// // Both roles are read from the realm, the scope is kept as is
+    /// let manage = authz::require_all([
+    ///     authz::require_any_role(["admin"]),
+    ///     authz::require_any_role(["manager"]),
+    ///     authz::require_scopes(["write:orders"]).with_claim("scope"),
+    /// ])
+    /// .with_claim("realm_access.roles");
+    /// ```
+    pub fn with_claim<C>(self, claim: C) -> Self
+    where
+        C: Into<Cow<'static, str>>,
+    {
+        let path = match claim.into() {
+            Cow::Borrowed(claim) => claim.split('.').map(Cow::Borrowed).collect(),
+            Cow::Owned(claim) => claim.split('.').map(|key| key.to_owned().into()).collect(),
+        };
+
+        self.set_claim(path)
+    }
+
+    /// Sets the claim to read the values from as a path of claim names.
+    ///
+    /// Unlike [`with_claim`](Require::with_claim), the names aren't split
+    /// by dots, so claims with dots in their names can be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axum_jwt::authz;
+    ///
+    /// // Auth0 namespaced claim
+    /// let admin = authz::require_any_role(["admin"]).with_claim_path(["https://example.com/roles"]);
+    ///
+    /// // Nested claim
+    /// let user = authz::require_any_role(["user"]).with_claim_path(["realm_access", "roles"]);
+    /// ```
+    pub fn with_claim_path<P, C>(self, path: P) -> Self
+    where
+        P: IntoIterator<Item = C>,
+        C: Into<Cow<'static, str>>,
+    {
+        self.set_claim(path.into_iter().map(C::into).collect())
+    }
+
+    fn set_claim(mut self, path: Vec<Cow<'static, str>>) -> Self {
+        Arc::make_mut(&mut self.0).claim = Some(path);
+        self
+    }
+
+    /// Checks if the claims satisfy the requirement.
+    ///
+    /// Returns [`Error::InsufficientScope`] if they don't.
+    pub fn check(&self, claims: &Value) -> Result<(), Error> {
+        if self.0.is_satisfied(claims, None) {
+            Ok(())
+        } else {
+            Err(Error::InsufficientScope)
+        }
+    }
+}
+
+/// Trait for declaring the required scopes at the type level.
+///
+/// Implement it for a marker type and use it with the [`RequireScope`]
+/// extractor.
+///
+/// # Examples
+///
+/// ```
+/// use axum_jwt::authz::ScopeSpec;
+///
+/// struct ReadOrders;
+///
+/// impl ScopeSpec for ReadOrders {
+///     const SCOPES: &'static [&'static str] = &["read:orders"];
+/// }
+///
+/// // Roles can be required as well
+/// struct Admin;
+///
+/// impl ScopeSpec for Admin {
+///     const CLAIM: &'static str = "realm_access.roles";
+///     const SCOPES: &'static [&'static str] = &["admin"];
+/// }
+/// ```
+pub trait ScopeSpec {
+    /// The dot-separated path to the claim with the scopes.
+    ///
+    /// The default is `scope`. For claim names containing dots, use
+    /// the [`Authorized`] extractor with
+    /// [`with_claim_path`](Require::with_claim_path) instead.
+    const CLAIM: &'static str = "scope";
+
+    /// The scopes, all of which are required.
+    const SCOPES: &'static [&'static str];
+}
+
+/// JWT [extractor] type requiring the [declared](ScopeSpec) scopes.
+///
+/// [extractor]: https://docs.rs/axum/latest/axum/extract/index.html
+///
+/// The token is validated the same way as by the [`Token`](crate::Token)
+/// extractor. If it lacks any of the scopes, the request is rejected with
+/// [`Error::InsufficientScope`]. Otherwise, the extractor returns the claims,
+/// like [`Claims`](crate::Claims). If you don't need the claims, omit
+/// the claims type.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum_jwt::authz::{RequireScope, ScopeSpec},
+///     serde::Deserialize,
+/// };
+///
+/// struct ReadOrders;
+///
+/// impl ScopeSpec for ReadOrders {
+///     const SCOPES: &'static [&'static str] = &["read:orders"];
+/// }
+///
+/// struct WriteOrders;
+///
+/// impl ScopeSpec for WriteOrders {
+///     const SCOPES: &'static [&'static str] = &["write:orders"];
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn orders(RequireScope(u, _): RequireScope<ReadOrders, User>) -> String {
+///     format!("Orders of {}", u.sub)
+/// }
+///
+/// async fn create_order(_: RequireScope<WriteOrders>) {}
+/// ```
+#[expect(clippy::type_complexity)]
+pub struct RequireScope<S, T = IgnoredAny, X = Bearer, V = Decoder>(
+    pub T,
+    pub PhantomData<fn() -> (S, X, V)>,
+);
+
+impl<S, T, X, V> RequireScope<S, T, X, V> {
+    /// Returns the claims.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<S, T, X, V> Clone for RequireScope<S, T, X, V>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), PhantomData)
+    }
+}
+
+impl<S, T, X, V> fmt::Debug for RequireScope<S, T, X, V>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RequireScope")
+            .field(&self.0)
+            .field(&any::type_name::<S>())
+            .finish()
+    }
+}
+
+impl<St, S, T, X, V> FromRequestParts<St> for RequireScope<S, T, X, V>
+where
+    St: Sync,
+    S: ScopeSpec,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<St> + Send + Sync + 'static,
+    V: Verifier + FromRef<St>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &St) -> Result<Self, Self::Rejection> {
+        // The scopes are checked in place, without building the requirement
+        let check = |claims: &Value| {
+            let claim = lookup(claims, S::CLAIM.split('.'));
+            if S::SCOPES.iter().all(|scope| contains(claim, scope)) {
+                Ok(())
+            } else {
+                Err(Error::InsufficientScope)
+            }
+        };
+
+        let claims = authorize::<_, _, _, X, V>(check, parts, state).await?;
+        Ok(Self(claims, PhantomData))
+    }
+}
+
+/// Trait for declaring a [requirement](Require) at the type level.
+///
+/// Implement it for a marker type and use it with the [`Authorized`]
+/// extractor. Unlike [`ScopeSpec`], it accepts any requirement, including
+/// the combined ones.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum_jwt::authz::{self, Require, Requirement},
+///     std::sync::LazyLock,
+/// };
+///
+/// struct Manager;
+///
+/// impl Requirement for Manager {
+///     fn require() -> Require {
+///         // Build the requirement once, cloning it is cheap
+///         static REQUIRE: LazyLock<Require> = LazyLock::new(|| {
+///             authz::require_any([
+///                 authz::require_scopes(["write:orders"]),
+///                 authz::require_any_role(["admin", "manager"]),
+///             ])
+///         });
+///
+///         REQUIRE.clone()
+///     }
+/// }
+/// ```
+pub trait Requirement {
+    /// Returns the requirement.
+    fn require() -> Require;
+}
+
+/// JWT [extractor] type requiring the [declared](Requirement) requirement.
+///
+/// [extractor]: https://docs.rs/axum/latest/axum/extract/index.html
+///
+/// The extractor works like [`RequireScope`], but accepts any requirement.
+/// If the token doesn't satisfy it, the request is rejected with
+/// [`Error::InsufficientScope`]. Otherwise, the extractor returns the claims.
+/// If you don't need the claims, omit the claims type.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum_jwt::authz::{self, Authorized, Require, Requirement},
+///     serde::Deserialize,
+/// };
+///
+/// struct Admin;
+///
+/// impl Requirement for Admin {
+///     fn require() -> Require {
+///         authz::require_any_role(["admin"]).with_claim("realm_access.roles")
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn users(Authorized(u, _): Authorized<Admin, User>) -> String {
+///     format!("Users for {}", u.sub)
+/// }
+///
+/// async fn delete_user(_: Authorized<Admin>) {}
+/// ```
+#[expect(clippy::type_complexity)]
+pub struct Authorized<R, T = IgnoredAny, X = Bearer, V = Decoder>(
+    pub T,
+    pub PhantomData<fn() -> (R, X, V)>,
+);
+
+impl<R, T, X, V> Authorized<R, T, X, V> {
+    /// Returns the claims.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<R, T, X, V> Clone for Authorized<R, T, X, V>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), PhantomData)
+    }
+}
+
+impl<R, T, X, V> fmt::Debug for Authorized<R, T, X, V>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Authorized")
+            .field(&self.0)
+            .field(&any::type_name::<R>())
+            .finish()
+    }
+}
+
+impl<St, R, T, X, V> FromRequestParts<St> for Authorized<R, T, X, V>
+where
+    St: Sync,
+    R: Requirement,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<St> + Send + Sync + 'static,
+    V: Verifier + FromRef<St>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &St) -> Result<Self, Self::Rejection> {
+        let require = R::require();
+        let claims =
+            authorize::<_, _, _, X, V>(|claims| require.check(claims), parts, state).await?;
+        Ok(Self(claims, PhantomData))
+    }
+}
+
+/// Verifies the token and checks the requirement, then deserializes
+/// the claims.
+async fn authorize<S, C, T, X, V>(check: C, parts: &mut Parts, state: &S) -> Result<T, Rejection>
+where
+    S: Sync,
+    C: FnOnce(&Value) -> Result<(), Error>,
+    T: DeserializeOwned,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
+{
+    let (_, data) = extract::verify::<_, Value, X, V>(parts, state).await?;
+    let verifier = V::from_ref(state);
+    check(&data.claims).map_err(|e| verify::rejection(&verifier, e))?;
+
+    let TokenData { claims, .. } =
+        verify::claims(&data).map_err(|e| verify::rejection(&verifier, e))?;
+
+    Ok(claims)
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    /// The claim set explicitly.
+    claim: Option<Vec<Cow<'static, str>>>,
+    kind: Kind,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Values {
+        /// The default claim.
+        claim: &'static str,
+        values: Vec<String>,
+        matches: Match,
+    },
+    All(Vec<Require>),
+    Any(Vec<Require>),
+}
+
+impl Rule {
+    /// Checks the claims.
+    ///
+    /// The claim of the rule takes precedence over the one inherited from
+    /// the outer rule.
+    fn is_satisfied(&self, claims: &Value, outer: Option<&[Cow<'static, str>]>) -> bool {
+        let claim = self.claim.as_deref().or(outer);
+        match &self.kind {
+            Kind::Values {
+                claim: default,
+                values,
+                matches,
+            } => {
+                let claim = match claim {
+                    Some(path) => lookup(claims, path.iter().map(AsRef::as_ref)),
+                    None => lookup(claims, [*default]),
+                };
+
+                let contains = |value: &String| contains(claim, value);
+                match matches {
+                    Match::All => values.iter().all(contains),
+                    Match::Any => values.iter().any(contains),
+                }
+            }
+            Kind::All(requirements) => requirements
+                .iter()
+                .all(|Require(rule)| rule.is_satisfied(claims, claim)),
+            Kind::Any(requirements) => requirements
+                .iter()
+                .any(|Require(rule)| rule.is_satisfied(claims, claim)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Match {
+    All,
+    Any,
+}
+
+/// Returns the claim at the path.
+fn lookup<'v, 'k, P>(claims: &'v Value, path: P) -> Option<&'v Value>
+where
+    P: IntoIterator<Item = &'k str>,
+{
+    path.into_iter()
+        .try_fold(claims, |value, key| value.get(key))
+}
+
+/// Checks if the claim contains the value.
+///
+/// The claim is either a space-delimited string or an array of strings.
+fn contains(claim: Option<&Value>, value: &str) -> bool {
+    match claim {
+        Some(Value::String(s)) => s.split_whitespace().any(|present| present == value),
+        Some(Value::Array(values)) => values.iter().any(|present| present == value),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn scope_forms() {
+        let require = require_scopes(["read", "write"]);
+        assert!(
+            require
+                .check(&json!({ "scope": "read  write admin" }))
+                .is_ok(),
+            "space-delimited string",
+        );
+
+        assert!(
+            require
+                .check(&json!({ "scope": ["write", "read"] }))
+                .is_ok(),
+            "array of strings",
+        );
+
+        assert!(
+            require.check(&json!({ "scope": "read" })).is_err(),
+            "a scope is missing",
+        );
+
+        assert!(
+            require.check(&json!({ "scope": "readwrite" })).is_err(),
+            "the scopes are delimited",
+        );
+    }
+
+    #[test]
+    fn nested_claim() {
+        let require = require_any_role(["admin"]).with_claim("realm_access.roles");
+        let claims = json!({ "realm_access": { "roles": ["user", "admin"] } });
+        assert!(require.check(&claims).is_ok(), "the nested claim");
+
+        let claims = json!({ "realm_access.roles": ["admin"] });
+        assert!(require.check(&claims).is_err(), "a dot is a separator");
+
+        let claims = json!({ "roles": ["admin"] });
+        assert!(
+            require.check(&claims).is_err(),
+            "the default claim isn't read"
+        );
+
+        let require = require_any_role(["admin"]).with_claim_path(["https://example.com/roles"]);
+        let claims = json!({ "https://example.com/roles": ["admin"] });
+        assert!(require.check(&claims).is_ok(), "the namespaced claim");
+
+        let claims = json!({ "https://example": { "com/roles": ["admin"] } });
+        assert!(require.check(&claims).is_err(), "a dot isn't a separator");
+
+        let require = require_any_role(["admin"]).with_claim_path(["realm_access", "roles"]);
+        let claims = json!({ "realm_access": { "roles": ["admin"] } });
+        assert!(require.check(&claims).is_ok(), "the nested claim path");
+    }
+
+    #[test]
+    fn missing_or_invalid_claim() {
+        let require = require_any_scope(["read"]);
+        for claims in [
+            json!({}),
+            json!({ "scope": null }),
+            json!({ "scope": 1 }),
+            json!({ "scope": { "read": true } }),
+            json!({ "scope": [1, true] }),
+            json!("read"),
+        ] {
+            assert!(
+                matches!(require.check(&claims), Err(Error::InsufficientScope)),
+                "{claims} is rejected",
+            );
+        }
+
+        let claims = json!({ "scope": ["read", 1] });
+        assert!(require.check(&claims).is_ok(), "other values are skipped");
+    }
+
+    #[test]
+    fn combined() {
+        let require = require_all([
+            require_scopes(["read"]),
+            require_any_role(["admin", "user"]),
+        ]);
+        let claims = json!({ "scope": "read", "roles": ["user"] });
+        assert!(require.check(&claims).is_ok(), "all are satisfied");
+
+        let claims = json!({ "scope": "read", "roles": ["guest"] });
+        assert!(require.check(&claims).is_err(), "one isn't satisfied");
+
+        let require = require_any([require_scopes(["admin"]), require_roles(["admin"])]);
+        let claims = json!({ "roles": ["admin"] });
+        assert!(require.check(&claims).is_ok(), "one is satisfied");
+
+        let claims = json!({ "scope": "read", "roles": ["user"] });
+        assert!(require.check(&claims).is_err(), "none is satisfied");
+
+        assert!(
+            require_all([]).check(&json!({})).is_ok(),
+            "nothing to satisfy"
+        );
+        assert!(
+            require_any([]).check(&json!({})).is_err(),
+            "nothing satisfies"
+        );
+    }
+
+    #[test]
+    fn combined_claim() {
+        let require = require_all([
+            require_any_role(["admin"]),
+            require_scopes(["read"]).with_claim("scp"),
+        ])
+        .with_claim("realm_access.roles");
+
+        let claims = json!({
+            "realm_access": { "roles": ["admin"] },
+            "scp": ["read"],
+        });
+
+        assert!(require.check(&claims).is_ok(), "the inner claim is kept");
+
+        let claims = json!({
+            "realm_access": { "roles": ["admin", "read"] },
+        });
+
+        assert!(require.check(&claims).is_err(), "the inner claim is read");
+
+        let require = require_any([require_any_role(["admin"]).with_claim("groups")])
+            .with_claim("roles")
+            .with_claim("permissions");
+
+        let claims = json!({ "groups": ["admin"] });
+        assert!(require.check(&claims).is_ok(), "the innermost claim wins");
+
+        let require = require_any_role(["admin"])
+            .with_claim("groups")
+            .with_claim("permissions");
+
+        let claims = json!({ "permissions": ["admin"] });
+        assert!(
+            require.check(&claims).is_ok(),
+            "the last claim of a leaf wins"
+        );
+
+        let require = require_any([require_any_role(["admin"])])
+            .with_claim("groups")
+            .with_claim("permissions");
+
+        assert!(
+            require.check(&claims).is_ok(),
+            "the last claim of a combined requirement wins"
+        );
+
+        let require =
+            require_all([require_any([require_any_role(["admin"])]).with_claim("groups")])
+                .with_claim("permissions");
+
+        let claims = json!({ "groups": ["admin"] });
+        assert!(
+            require.check(&claims).is_ok(),
+            "the claim of an inner combined requirement wins"
+        );
+    }
+}
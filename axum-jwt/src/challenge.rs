@@ -0,0 +1,352 @@
+use {
+    crate::error::Error,
+    axum_core::response::{IntoResponse, Response},
+    http::{HeaderValue, StatusCode, header},
+    jsonwebtoken::errors::ErrorKind,
+    std::{borrow::Cow, fmt::Write},
+};
+
+/// The [RFC 6750] authentication challenge.
+///
+/// [RFC 6750]: https://datatracker.ietf.org/doc/html/rfc6750#section-3
+///
+/// When authentication fails, the response contains
+/// the `WWW-Authenticate` header with the `Bearer` challenge, so OAuth
+/// clients can tell, for example, an expired token from a malformed one:
+///
+/// * If the request has no token, the challenge has no error code.
+/// * If the request carries more than one token, the status is
+///   `400 Bad Request` and the error code is `invalid_request`.
+/// * If the token is invalid, the status is `401 Unauthorized` and
+///   the error code is `invalid_token`, with the error description
+///   explaining the reason.
+/// * If the token lacks the required scope, the status is `403 Forbidden`
+///   and the error code is `insufficient_scope`.
+/// * If the token can't be verified at the moment, the status is
+///   `503 Service Unavailable` without the challenge.
+///
+/// The challenge can additionally include the realm and the scope required
+/// to access the resource. To configure it, pass it to the
+/// [`with_challenge`](crate::Decoder::with_challenge) method of the decoder.
+/// Both the extractors and the [middleware](crate::layer) use the challenge
+/// of the decoder.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{Router, routing},
+///     axum_jwt::{Challenge, Decoder, jsonwebtoken::DecodingKey},
+/// };
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"))
+///     .with_challenge(Challenge::new().with_realm("example").with_scope("read write"));
+///
+/// # async fn hello() {}
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .with_state(decoder);
+/// # let _: Router = app;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Challenge {
+    realm: Option<Cow<'static, str>>,
+    scope: Option<Cow<'static, str>>,
+}
+
+impl Challenge {
+    /// Creates a challenge without the realm and scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the `realm` parameter.
+    pub fn with_realm<R>(mut self, realm: R) -> Self
+    where
+        R: Into<Cow<'static, str>>,
+    {
+        self.realm = Some(realm.into());
+        self
+    }
+
+    /// Sets the `scope` parameter.
+    ///
+    /// The scope is a space-delimited list of scope values required to
+    /// access the resource.
+    pub fn with_scope<S>(mut self, scope: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Returns the realm.
+    pub fn realm(&self) -> Option<&str> {
+        self.realm.as_deref()
+    }
+
+    /// Returns the scope.
+    pub fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    /// Renders the error into a response with the challenge.
+    pub fn render<U>(&self, error: Error<U>) -> Response
+    where
+        U: IntoResponse,
+    {
+        let invalid = |description| {
+            (
+                StatusCode::UNAUTHORIZED,
+                Some("invalid_token"),
+                Some(description),
+            )
+        };
+
+        let (status, code, description) = match error {
+            Error::Missing | Error::Scheme => (StatusCode::UNAUTHORIZED, None, None),
+            Error::Ambiguous => (
+                StatusCode::BAD_REQUEST,
+                Some("invalid_request"),
+                Some("The request carries more than one token"),
+            ),
+            Error::Expired => invalid("The token has expired"),
+            Error::NotYetValid => invalid("The token is not yet valid"),
+            Error::Signature => invalid("The token signature is invalid"),
+            Error::UnknownKey => invalid("The token is signed with an unknown key"),
+            Error::Audience => invalid("The token audience is invalid"),
+            Error::Issuer => invalid("The token issuer is invalid"),
+            Error::Jwt(e) => invalid(describe(e.kind())),
+            Error::Revoked => invalid("The token has been revoked"),
+            Error::Inactive => invalid("The token is not active"),
+            Error::Rejected => invalid("The token is rejected"),
+            Error::InsufficientScope => (
+                StatusCode::FORBIDDEN,
+                Some("insufficient_scope"),
+                Some("The token lacks the required scope"),
+            ),
+            Error::Csrf => return StatusCode::FORBIDDEN.into_response(),
+            Error::Unavailable => return StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            Error::Custom(u) => return u.into_response(),
+        };
+
+        let mut res = status.into_response();
+        if let Ok(value) = HeaderValue::try_from(self.header(code, description)) {
+            res.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+        }
+
+        res
+    }
+
+    fn header(&self, code: Option<&str>, description: Option<&str>) -> String {
+        let params = [
+            ("realm", self.realm()),
+            ("scope", self.scope()),
+            ("error", code),
+            ("error_description", description),
+        ];
+
+        let mut header = String::from("Bearer");
+        let params = params
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value?)));
+
+        for (n, (name, value)) in params.enumerate() {
+            let sep = if n == 0 { " " } else { ", " };
+            _ = write!(header, "{sep}{name}=\"");
+            for c in value.chars() {
+                if matches!(c, '"' | '\\') {
+                    header.push('\\');
+                }
+
+                header.push(c);
+            }
+
+            header.push('"');
+        }
+
+        header
+    }
+}
+
+/// Trait for rendering authentication errors into responses.
+///
+/// To use a custom renderer, pass it to the
+/// [`with_render`](crate::Decoder::with_render) method of the decoder.
+/// The trait is implemented for [`Challenge`], which is the default, and for
+/// functions.
+///
+/// # Examples
+///
+/// Render errors as [RFC 7807] problem details:
+///
+/// [RFC 7807]: https://datatracker.ietf.org/doc/html/rfc7807
+///
+/// ```
+/// use {
+///     axum::{
+///         Router,
+///         http::{StatusCode, header},
+///         response::{IntoResponse, Response},
+///         routing,
+///     },
+///     axum_jwt::{Decoder, Error, jsonwebtoken::DecodingKey},
+/// };
+///
+/// fn problem(error: Error<Response>) -> Response {
+///     let (status, detail) = match error {
+///         Error::Missing => (StatusCode::UNAUTHORIZED, "The token is missing"),
+///         Error::Csrf => (StatusCode::FORBIDDEN, "The CSRF check failed"),
+///         Error::InsufficientScope => (StatusCode::FORBIDDEN, "The scope is insufficient"),
+///         Error::Custom(res) => return res,
+///         _ => (StatusCode::UNAUTHORIZED, "The token is invalid"),
+///     };
+///
+///     let body = format!(r#"{{"status":{},"detail":"{detail}"}}"#, status.as_u16());
+///     (status, [(header::CONTENT_TYPE, "application/problem+json")], body).into_response()
+/// }
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret")).with_render(problem);
+///
+/// # async fn hello() {}
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .with_state(decoder);
+/// # let _: Router = app;
+/// ```
+pub trait Render: Send + Sync + 'static {
+    fn render(&self, error: Error<Response>) -> Response;
+}
+
+impl Render for Challenge {
+    fn render(&self, error: Error<Response>) -> Response {
+        Self::render(self, error)
+    }
+}
+
+impl<F> Render for F
+where
+    F: Fn(Error<Response>) -> Response + Send + Sync + 'static,
+{
+    fn render(&self, error: Error<Response>) -> Response {
+        self(error)
+    }
+}
+
+fn describe(kind: &ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::InvalidSubject => "The token subject is invalid",
+        ErrorKind::MissingRequiredClaim(_) => "The token is missing a required claim",
+        ErrorKind::InvalidAlgorithm => "The token algorithm is not allowed",
+        _ => "The token is malformed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(error: Error, challenge: &Challenge) -> (StatusCode, Option<String>) {
+        let res = challenge.render(error);
+        let header = res.headers().get(header::WWW_AUTHENTICATE).map(|value| {
+            value
+                .to_str()
+                .expect("the header must be a string")
+                .to_owned()
+        });
+
+        (res.status(), header)
+    }
+
+    #[test]
+    fn header() {
+        let challenge = Challenge::new();
+        assert_eq!(challenge.header(None, None), "Bearer", "no parameters");
+
+        let challenge = challenge.with_realm("example").with_scope("read write");
+        assert_eq!(
+            challenge.header(Some("invalid_token"), Some("The token has expired")),
+            "Bearer realm=\"example\", scope=\"read write\", error=\"invalid_token\", \
+            error_description=\"The token has expired\"",
+            "all parameters",
+        );
+
+        let challenge = Challenge::new().with_realm(r#"a "quoted" \ realm"#);
+        assert_eq!(
+            challenge.header(None, None),
+            r#"Bearer realm="a \"quoted\" \\ realm""#,
+            "escaped",
+        );
+    }
+
+    #[test]
+    fn render() {
+        let default = Challenge::new();
+        assert_eq!(
+            challenge(Error::Expired, &default),
+            (
+                StatusCode::UNAUTHORIZED,
+                Some(
+                    "Bearer error=\"invalid_token\", \
+                    error_description=\"The token has expired\""
+                        .to_owned()
+                ),
+            ),
+            "expired",
+        );
+
+        let malformed = Error::Jwt(ErrorKind::InvalidToken.into());
+        assert_eq!(
+            challenge(malformed, &default),
+            (
+                StatusCode::UNAUTHORIZED,
+                Some(
+                    "Bearer error=\"invalid_token\", \
+                    error_description=\"The token is malformed\""
+                        .to_owned()
+                ),
+            ),
+            "malformed",
+        );
+
+        let realm = Challenge::new().with_realm("example");
+        assert_eq!(
+            challenge(Error::Missing, &realm),
+            (
+                StatusCode::UNAUTHORIZED,
+                Some("Bearer realm=\"example\"".to_owned())
+            ),
+            "no error code without a token",
+        );
+
+        let (status, header) = challenge(Error::Ambiguous, &default);
+        assert_eq!(status, StatusCode::BAD_REQUEST, "ambiguous");
+        assert!(
+            header.is_some_and(|header| header.contains("error=\"invalid_request\"")),
+            "invalid request",
+        );
+
+        let scope = Challenge::new().with_scope("read");
+        let (status, header) = challenge(Error::InsufficientScope, &scope);
+        assert_eq!(status, StatusCode::FORBIDDEN, "insufficient scope");
+        assert!(
+            header.is_some_and(|header| {
+                header.starts_with("Bearer scope=\"read\", error=\"insufficient_scope\"")
+            }),
+            "the required scope",
+        );
+
+        assert_eq!(
+            challenge(Error::Csrf, &default),
+            (StatusCode::FORBIDDEN, None),
+            "no challenge for CSRF",
+        );
+
+        assert_eq!(
+            challenge(Error::Unavailable, &default),
+            (StatusCode::SERVICE_UNAVAILABLE, None),
+            "no challenge if unavailable",
+        );
+    }
+}
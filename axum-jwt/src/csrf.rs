@@ -0,0 +1,190 @@
+use {
+    crate::{error::Error, extract::Cookie},
+    http::{HeaderMap, HeaderName},
+    serde_json::Value,
+    std::borrow::Cow,
+};
+
+/// The CSRF protection for the [middleware](crate::layer).
+///
+/// When the token is stored in a cookie, the browser sends it with every
+/// request, including the ones initiated by other sites. The protection
+/// follows the double-submit pattern: the client must additionally send
+/// a CSRF token in the `X-CSRF-Token` header, and the middleware compares it
+/// with the expected value. The expected value is taken either from a claim
+/// of the JWT or from a second cookie, which is readable by the client code,
+/// but can't be read by other sites.
+///
+/// Only requests with unsafe methods, like `POST` or `DELETE`, are checked.
+/// If the check fails, the request is rejected with [`Error::Csrf`].
+///
+/// To enable the protection, pass it to the
+/// [`with_csrf`](crate::layer::JwtLayer::with_csrf) method.
+#[derive(Clone, Debug)]
+pub struct Csrf {
+    header: HeaderName,
+    source: Source,
+}
+
+impl Csrf {
+    /// Creates the protection comparing the header with the claim of
+    /// the provided name.
+    ///
+    /// The claim must be a string.
+    pub fn claim<C>(claim: C) -> Self
+    where
+        C: Into<Cow<'static, str>>,
+    {
+        Self::new(Source::Claim(claim.into()))
+    }
+
+    /// Creates the protection comparing the header with the cookie of
+    /// the provided name.
+    pub fn cookie<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self::new(Source::Cookie(Cookie::new(name)))
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            header: HeaderName::from_static("x-csrf-token"),
+            source,
+        }
+    }
+
+    /// Sets the name of the header containing the CSRF token.
+    ///
+    /// The default is `X-CSRF-Token`.
+    pub fn with_header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Returns the expected value if it's stored in a claim of the token.
+    pub(crate) fn expected<'c>(&self, claims: &'c Value) -> Result<Option<&'c str>, Error> {
+        let Source::Claim(claim) = &self.source else {
+            return Ok(None);
+        };
+
+        match claims.get(&**claim) {
+            Some(Value::String(expected)) => Ok(Some(expected)),
+            _ => Err(Error::Csrf),
+        }
+    }
+
+    /// Compares the header with the expected value.
+    ///
+    /// An empty expected value never matches.
+    pub(crate) fn verify(&self, expected: Option<&str>, headers: &HeaderMap) -> Result<(), Error> {
+        let expected = match &self.source {
+            Source::Claim(_) => expected,
+            Source::Cookie(cookie) => cookie.find(headers),
+        };
+
+        let actual = headers.get(&self.header).map(|value| value.as_bytes());
+        match (expected, actual) {
+            (Some(expected), Some(actual))
+                if !expected.is_empty() && eq(expected.as_bytes(), actual) =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::Csrf),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+    Claim(Cow<'static, str>),
+    Cookie(Cookie),
+}
+
+/// Compares the values in constant time to not leak the expected value.
+fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, http::HeaderValue, serde_json::json};
+
+    fn headers<const N: usize>(pairs: [(&'static str, &'static str); N]) -> HeaderMap {
+        pairs
+            .into_iter()
+            .map(|(name, value)| {
+                let name = HeaderName::from_static(name);
+                (name, HeaderValue::from_static(value))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compare() {
+        assert!(eq(b"secret", b"secret"), "equal values");
+        assert!(!eq(b"secret", b"secreT"), "the last byte differs");
+        assert!(!eq(b"secret", b"Secret"), "the first byte differs");
+        assert!(!eq(b"secret", b"secret2"), "a longer value");
+        assert!(!eq(b"secret", b"secre"), "a prefix");
+        assert!(eq(b"", b""), "empty values");
+    }
+
+    #[test]
+    fn verify_claim() {
+        let csrf = Csrf::claim("csrf");
+        let claims = json!({ "csrf": "abc", "n": 1 });
+        let expected = csrf.expected(&claims).expect("the claim");
+        assert_eq!(expected, Some("abc"), "the expected value");
+
+        let ok = headers([("x-csrf-token", "abc")]);
+        assert!(csrf.verify(expected, &ok).is_ok(), "the header matches");
+
+        let wrong = headers([("x-csrf-token", "abd")]);
+        assert!(
+            matches!(csrf.verify(expected, &wrong), Err(Error::Csrf)),
+            "the header differs",
+        );
+
+        assert!(
+            matches!(csrf.verify(expected, &HeaderMap::new()), Err(Error::Csrf)),
+            "no header",
+        );
+
+        assert!(
+            matches!(Csrf::claim("n").expected(&claims), Err(Error::Csrf)),
+            "not a string",
+        );
+
+        assert!(
+            matches!(Csrf::claim("none").expected(&claims), Err(Error::Csrf)),
+            "no claim",
+        );
+    }
+
+    #[test]
+    fn verify_cookie() {
+        let csrf = Csrf::cookie("csrf").with_header(HeaderName::from_static("x-xsrf"));
+        assert_eq!(
+            csrf.expected(&json!({ "csrf": "abc" }))
+                .expect("no claim is needed"),
+            None,
+            "the cookie is used instead",
+        );
+
+        let ok = headers([("cookie", "a=1; csrf=abc"), ("x-xsrf", "abc")]);
+        assert!(csrf.verify(None, &ok).is_ok(), "the header matches");
+
+        let default = headers([("cookie", "csrf=abc"), ("x-csrf-token", "abc")]);
+        assert!(csrf.verify(None, &default).is_err(), "the custom header");
+
+        let wrong = headers([("cookie", "csrf=abc"), ("x-xsrf", "abcd")]);
+        assert!(csrf.verify(None, &wrong).is_err(), "the header differs");
+
+        let missing = headers([("x-xsrf", "abc")]);
+        assert!(csrf.verify(None, &missing).is_err(), "no cookie");
+
+        let empty = headers([("cookie", "csrf="), ("x-xsrf", "")]);
+        assert!(csrf.verify(None, &empty).is_err(), "empty values");
+    }
+}
@@ -1,8 +1,27 @@
 use {
+    crate::{
+        challenge::{Challenge, Render},
+        error::Error,
+        extract::Optional,
+        revoke::{DynRevocation, Registered, Revocation},
+        verify::{self, Verifier},
+    },
+    arc_swap::ArcSwap,
     axum_core::extract::FromRef,
-    jsonwebtoken::{DecodingKey, TokenData, Validation},
-    serde::de::DeserializeOwned,
-    std::{fmt, ops::Deref, sync::Arc},
+    jsonwebtoken::{
+        Algorithm, DecodingKey, TokenData, Validation,
+        errors::ErrorKind,
+        jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet},
+    },
+    serde::{Deserialize, de::DeserializeOwned},
+    serde_json::Value,
+    std::{
+        collections::HashMap,
+        fmt,
+        ops::Deref,
+        str::FromStr,
+        sync::{Arc, Weak},
+    },
 };
 
 /// A decoder for JSON Web Tokens (JWTs).
@@ -77,23 +96,20 @@ use {
 /// # let _: Router = app;
 /// ```
 #[derive(Clone)]
-pub struct Decoder(Arc<Inner>);
+pub struct Decoder {
+    shared: Arc<Shared>,
+    settings: Arc<Settings>,
+}
 
 impl Decoder {
     /// Creates a decoder from the provided decoding key.
     pub fn from_key(key: DecodingKey) -> Self {
-        Self(Arc::new(Inner {
-            keys: vec![key],
-            validation: Validation::default(),
-        }))
+        Self::from_inner(Inner::new(Keys::list(vec![key]), Validation::default()))
     }
 
     /// Creates a decoder from the provided decoding key and validation.
     pub fn new(key: DecodingKey, validation: Validation) -> Self {
-        Self(Arc::new(Inner {
-            keys: vec![key],
-            validation,
-        }))
+        Self::from_inner(Inner::new(Keys::list(vec![key]), validation))
     }
 
     /// Creates a decoder from the provided decoding keys and validation.
@@ -103,42 +119,402 @@ impl Decoder {
         if keys.is_empty() {
             None
         } else {
-            Some(Self(Arc::new(Inner { keys, validation })))
+            Some(Self::from_inner(Inner::new(Keys::list(keys), validation)))
+        }
+    }
+
+    /// Creates a decoder from the provided [JWK set](JwkSet) and validation.
+    ///
+    /// Unlike [`with_keys`](Decoder::with_keys), the decoder doesn't try every
+    /// key in turn. Instead, the keys are indexed by their `kid` parameter and
+    /// exactly one key is selected using the `kid` field of the token header.
+    /// Tokens without a `kid` or with a `kid` missing from the set are
+    /// rejected with [`Error::UnknownKey`]. Keys without a `kid` are skipped,
+    /// since they can never be selected.
+    ///
+    /// The algorithms of the validation are restricted for each key to
+    /// the ones it can be used with: the algorithms of its key type and curve,
+    /// narrowed down to the one from its `alg` parameter, if it's present.
+    /// This way a set can mix keys of different types, for example, RSA and
+    /// EC keys.
+    ///
+    /// Returns an error if any of the keys can't be converted to
+    /// a [decoding key](DecodingKey), if two keys share the same `kid` or if
+    /// the set has no key with a `kid`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axum_jwt::{
+    ///     Decoder,
+    ///     jsonwebtoken::{Algorithm, Validation, jwk::JwkSet},
+    /// };
+    ///
+    /// # fn fetch_jwks() -> JwkSet {
+    /// #     let jwks = r#"{"keys":[{"kty":"oct","kid":"key","k":"c2VjcmV0"}]}"#;
+    /// #     serde_json::from_str(jwks).expect("the set must be valid")
+    /// # }
+    /// // The set is usually published by an identity provider.
+    /// let jwks: JwkSet = fetch_jwks();
+    ///
+    /// let decoder = Decoder::from_jwks(&jwks, Validation::new(Algorithm::RS256))?;
+    /// # Ok::<_, axum_jwt::JwksError>(())
+    /// ```
+    pub fn from_jwks(jwks: &JwkSet, validation: Validation) -> Result<Self, JwksError> {
+        Ok(Self::from_inner(Inner::new(
+            Keys::from_jwks(jwks)?,
+            validation,
+        )))
+    }
+
+    /// Atomically replaces the decoding keys.
+    ///
+    /// The decoder is a handle to shared state, so the change is observed by
+    /// all its clones, including the ones already passed to the router state
+    /// or to the [middleware](crate::layer). Decoding doesn't take any lock,
+    /// tokens which are being decoded at the moment of replacement are
+    /// decoded with the previous keys.
+    ///
+    /// Like [`with_keys`](Decoder::with_keys), an empty vector is rejected:
+    /// the method returns `false` and the keys remain unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, routing},
+    ///     axum_jwt::{Decoder, jsonwebtoken::DecodingKey},
+    /// };
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// # async fn hello() {}
+    /// let app = Router::new()
+    ///     .route("/", routing::get(hello))
+    ///     .with_state(decoder.clone());
+    /// # let _: Router = app;
+    ///
+    /// // Later, rotate the keys without rebuilding the router
+    /// let replaced = decoder.replace_keys(vec![
+    ///     DecodingKey::from_secret(b"new secret"),
+    ///     DecodingKey::from_secret(b"secret"),
+    /// ]);
+    ///
+    /// assert!(replaced);
+    /// ```
+    pub fn replace_keys(&self, keys: Vec<DecodingKey>) -> bool {
+        if keys.is_empty() {
+            return false;
+        }
+
+        self.shared.replace_keys(Keys::list(keys));
+        true
+    }
+
+    /// Atomically replaces the decoding keys with the provided
+    /// [JWK set](JwkSet).
+    ///
+    /// After the replacement, the keys are selected by `kid` the same way as
+    /// for the decoder created with [`from_jwks`](Decoder::from_jwks).
+    ///
+    /// Returns an error in the same cases as [`from_jwks`](Decoder::from_jwks).
+    /// In this case, the keys remain unchanged.
+    pub fn replace_jwks(&self, jwks: &JwkSet) -> Result<(), JwksError> {
+        self.shared.replace_keys(Keys::from_jwks(jwks)?);
+        Ok(())
+    }
+
+    /// Atomically replaces the validation.
+    ///
+    /// Like [`replace_keys`](Decoder::replace_keys), the change is observed
+    /// by all clones of the decoder.
+    pub fn replace_validation(&self, validation: Validation) {
+        self.shared
+            .inner
+            .rcu(|inner| Inner::new(inner.keys.clone(), validation.clone()));
+    }
+
+    /// Sets the [revocation](Revocation) check.
+    ///
+    /// After a token is successfully decoded, both the extractors and
+    /// the [middleware](crate::layer) consult the revocation check and reject
+    /// the token with [`Error::Revoked`] if it has been revoked.
+    ///
+    /// Unlike the keys, the revocation check is only set on the returned
+    /// decoder and its later clones. The clones made before calling this
+    /// method keep their settings, but still share the keys with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, routing},
+    ///     axum_jwt::{Decoder, jsonwebtoken::DecodingKey, revoke::Denylist},
+    /// };
+    ///
+    /// let denylist = Denylist::new();
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"))
+    ///     .with_revocation(denylist.clone());
+    ///
+    /// # async fn hello() {}
+    /// let app = Router::new()
+    ///     .route("/", routing::get(hello))
+    ///     .with_state(decoder);
+    /// # let _: Router = app;
+    /// ```
+    pub fn with_revocation<R>(mut self, revocation: R) -> Self
+    where
+        R: Revocation,
+    {
+        Arc::make_mut(&mut self.settings).revocation = Some(Arc::new(revocation));
+        self
+    }
+
+    /// Sets the [challenge](Challenge) used to render authentication errors.
+    ///
+    /// This is a shorthand for [`with_render`](Decoder::with_render) with
+    /// the challenge.
+    pub fn with_challenge(self, challenge: Challenge) -> Self {
+        self.with_render(challenge)
+    }
+
+    /// Sets the [renderer](Render) of authentication errors.
+    ///
+    /// Both the extractors and the [middleware](crate::layer) use it to
+    /// render a response when authentication fails. By default, errors are
+    /// rendered with the [RFC 6750](Challenge) challenge.
+    ///
+    /// Like the revocation check, the renderer is only set on the returned
+    /// decoder.
+    ///
+    /// # Examples
+    ///
+    /// See the [`Render`] trait.
+    pub fn with_render<R>(mut self, render: R) -> Self
+    where
+        R: Render,
+    {
+        Arc::make_mut(&mut self.settings).render = Arc::new(render);
+        self
+    }
+
+    /// Sets how optional extractors treat invalid tokens.
+    ///
+    /// An optional extractor, like `Option<Claims<T>>`, returns `None` if
+    /// the request carries no token. By default, if the token is present
+    /// but invalid, the request is still rejected. With [`Optional::Lenient`]
+    /// an invalid token is treated as no token at all, while other errors,
+    /// like an unavailable verifier, still reject the request.
+    ///
+    /// Like the revocation check, the setting is only applied to
+    /// the returned decoder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, routing},
+    ///     axum_jwt::{Claims, Decoder, Optional, jsonwebtoken::DecodingKey},
+    ///     serde::Deserialize,
+    /// };
+    ///
+    /// #[derive(Deserialize)]
+    /// struct User {
+    ///     sub: String,
+    /// }
+    ///
+    /// async fn hello(claims: Option<Claims<User>>) -> String {
+    ///     match claims {
+    ///         Some(Claims(u)) => format!("Hello, {}!", u.sub),
+    ///         None => "Hello, Anonimus!".to_owned(),
+    ///     }
+    /// }
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"))
+    ///     .with_optional(Optional::Lenient);
+    ///
+    /// let app = Router::new()
+    ///     .route("/", routing::get(hello))
+    ///     .with_state(decoder);
+    /// # let _: Router = app;
+    /// ```
+    pub fn with_optional(mut self, optional: Optional) -> Self {
+        Arc::make_mut(&mut self.settings).optional = optional;
+        self
+    }
+
+    /// Returns a snapshot of the current decoding keys.
+    ///
+    /// Since the keys can be [replaced](Decoder::replace_keys) at any time,
+    /// the method returns a snapshot instead of a reference to the slice.
+    /// The snapshot isn't affected by later replacements of the keys.
+    /// Dereference it to get the slice:
+    ///
+    /// ```
+    /// use axum_jwt::{Decoder, jsonwebtoken::DecodingKey};
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    /// let keys: &[DecodingKey] = &decoder.keys();
+    /// assert_eq!(keys.len(), 1);
+    /// ```
+    pub fn keys(&self) -> impl Deref<Target = [DecodingKey]> + use<> {
+        Snapshot {
+            inner: self.shared.inner.load_full(),
+            get: |inner| &inner.keys.keys[..],
+        }
+    }
+
+    /// Returns a snapshot of the current validation.
+    ///
+    /// Like [`keys`](Decoder::keys), the method returns a snapshot instead
+    /// of a reference, which isn't affected by later replacements of
+    /// the validation.
+    pub fn validation(&self) -> impl Deref<Target = Validation> + use<> {
+        Snapshot {
+            inner: self.shared.inner.load_full(),
+            get: |inner| &inner.validation,
+        }
+    }
+
+    fn from_inner(inner: Inner) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                inner: ArcSwap::from_pointee(inner),
+                refresh: None,
+            }),
+            settings: Arc::new(Settings::default()),
         }
     }
 
-    /// Returns a slice of decoding keys.
-    pub fn keys(&self) -> &[DecodingKey] {
-        &self.0.keys
+    /// Creates a decoder that calls `refresh` each time a token with
+    /// an unknown `kid` is decoded.
+    #[cfg_attr(not(any(feature = "remote", test)), expect(dead_code))]
+    pub(crate) fn with_refresh<R>(
+        jwks: &JwkSet,
+        validation: Validation,
+        refresh: R,
+    ) -> Result<Self, JwksError>
+    where
+        R: Fn() + Send + Sync + 'static,
+    {
+        Ok(Self {
+            shared: Arc::new(Shared {
+                inner: ArcSwap::from_pointee(Inner::new(Keys::from_jwks(jwks)?, validation)),
+                refresh: Some(Box::new(refresh)),
+            }),
+            settings: Arc::new(Settings::default()),
+        })
     }
 
-    /// Returns a reference to the validation.
-    pub fn validation(&self) -> &Validation {
-        &self.0.validation
+    #[cfg_attr(not(feature = "remote"), expect(dead_code))]
+    pub(crate) fn downgrade(&self) -> Weak<Shared> {
+        Arc::downgrade(&self.shared)
     }
 
-    pub(crate) fn decode<T>(&self, token: &str) -> Result<TokenData<T>, jsonwebtoken::errors::Error>
+    pub(crate) fn decode<T>(&self, token: &str) -> Result<TokenData<T>, Error>
     where
         T: DeserializeOwned,
     {
-        let decoder = &*self.0;
+        let decoder = self.shared.inner.load();
+        let Inner {
+            keys,
+            validation,
+            restricted,
+        } = &**decoder;
+        if let Some(kids) = &keys.kids {
+            let header = jsonwebtoken::decode_header(token).map_err(Error::from)?;
+            // A token without `kid` can't be decoded with any set,
+            // so only an unseen `kid` is worth refetching the keys
+            let kid = header.kid.ok_or(Error::UnknownKey)?;
+            let Some(&index) = kids.get(&kid) else {
+                if let Some(refresh) = &self.shared.refresh {
+                    refresh();
+                }
+
+                return Err(Error::UnknownKey);
+            };
+
+            let validation = &restricted[index];
+            if validation.algorithms.is_empty() {
+                return Err(Error::from(jsonwebtoken::errors::Error::from(
+                    ErrorKind::InvalidAlgorithm,
+                )));
+            }
+
+            let key = &keys.keys[index];
+            return jsonwebtoken::decode(token, key, validation).map_err(Error::from);
+        }
+
         let mut err = None;
-        for key in &decoder.keys {
-            match jsonwebtoken::decode(token, key, &decoder.validation) {
+        for key in &keys.keys {
+            match jsonwebtoken::decode(token, key, validation) {
                 Ok(data) => return Ok(data),
                 Err(e) => err = Some(e),
             }
         }
 
-        Err(err.expect("take error"))
+        Err(err.map_or(Error::UnknownKey, Error::from))
+    }
+}
+
+impl Verifier for Decoder {
+    async fn verify(&self, token: &str) -> Result<TokenData<Value>, Error> {
+        let data = self.decode(token)?;
+        if let Some(revocation) = &self.settings.revocation {
+            let claims =
+                Registered::deserialize(&data.claims).map_err(jsonwebtoken::errors::Error::from)?;
+            if revocation.is_revoked(&claims).await {
+                return Err(Error::Revoked);
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// The claims are decoded directly into the requested type, unless
+    /// the revocation check needs the registered claims.
+    async fn verify_as<T>(&self, token: &str) -> Result<TokenData<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        match &self.settings.revocation {
+            Some(_) => verify::claims(&self.verify(token).await?),
+            None => self.decode(token),
+        }
+    }
+
+    /// The token is verified immediately, unless the revocation check
+    /// is set.
+    fn verify_now(&self, token: &str) -> Option<Result<TokenData<Value>, Error>> {
+        self.settings
+            .revocation
+            .is_none()
+            .then(|| self.decode(token))
+    }
+
+    fn render(&self) -> Arc<dyn Render> {
+        self.settings.render.clone()
+    }
+
+    fn optional(&self) -> Optional {
+        self.settings.optional
+    }
+
+    /// Clones of the same decoder validate tokens the same way.
+    fn is_same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared) && Arc::ptr_eq(&self.settings, &other.settings)
     }
 }
 
 impl fmt::Debug for Decoder {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let settings = &self.settings;
         f.debug_struct("Decoder")
             .field("keys", &"..")
-            .field("validation", &self.0.validation)
+            .field("validation", &self.shared.inner.load().validation)
+            .field("revocation", &settings.revocation.as_ref().map(|_| ".."))
+            .field("render", &"..")
+            .field("optional", &settings.optional)
             .finish()
     }
 }
@@ -152,7 +528,570 @@ where
     }
 }
 
+pub(crate) struct Shared {
+    inner: ArcSwap<Inner>,
+    refresh: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+impl Shared {
+    /// Atomically replaces the keys, keeping the current validation.
+    pub(crate) fn replace_keys(&self, keys: Keys) {
+        self.inner
+            .rcu(|inner| Inner::new(keys.clone(), inner.validation.clone()));
+    }
+}
+
+/// The settings of the decoder, which are copied on write.
+#[derive(Clone)]
+struct Settings {
+    revocation: Option<Arc<dyn DynRevocation>>,
+    render: Arc<dyn Render>,
+    optional: Optional,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            revocation: None,
+            render: Arc::new(Challenge::default()),
+            optional: Optional::Strict,
+        }
+    }
+}
+
+/// A snapshot of the decoder state.
+struct Snapshot<T>
+where
+    T: ?Sized,
+{
+    inner: Arc<Inner>,
+    get: fn(&Inner) -> &T,
+}
+
+impl<T> Deref for Snapshot<T>
+where
+    T: ?Sized,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        (self.get)(&self.inner)
+    }
+}
+
 struct Inner {
-    keys: Vec<DecodingKey>,
+    keys: Keys,
     validation: Validation,
+
+    /// The validation of each key restricted to the algorithms of the key.
+    restricted: Vec<Validation>,
+}
+
+impl Inner {
+    fn new(keys: Keys, validation: Validation) -> Self {
+        let restricted = keys
+            .algorithms
+            .iter()
+            .map(|algorithms| {
+                let mut validation = validation.clone();
+                validation.algorithms.retain(|alg| algorithms.contains(alg));
+                validation
+            })
+            .collect();
+
+        Self {
+            keys,
+            validation,
+            restricted,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Keys {
+    keys: Vec<DecodingKey>,
+    kids: Option<HashMap<String, usize>>,
+
+    /// The algorithms each key of the set can be used with.
+    algorithms: Vec<Vec<Algorithm>>,
+}
+
+impl Keys {
+    fn list(keys: Vec<DecodingKey>) -> Self {
+        Self {
+            keys,
+            kids: None,
+            algorithms: vec![],
+        }
+    }
+
+    pub(crate) fn from_jwks(jwks: &JwkSet) -> Result<Self, JwksError> {
+        let mut keys = vec![];
+        let mut kids = HashMap::new();
+        let mut algorithms = vec![];
+        for jwk in &jwks.keys {
+            let Some(kid) = &jwk.common.key_id else {
+                continue;
+            };
+
+            if kids.insert(kid.clone(), keys.len()).is_some() {
+                return Err(JwksError::DuplicateKid(kid.clone()));
+            }
+
+            keys.push(DecodingKey::from_jwk(jwk).map_err(JwksError::Jwk)?);
+            algorithms.push(self::algorithms(jwk));
+        }
+
+        if keys.is_empty() {
+            return Err(JwksError::Empty);
+        }
+
+        Ok(Self {
+            keys,
+            kids: Some(kids),
+            algorithms,
+        })
+    }
+}
+
+/// An error of creating decoding keys from a [JWK set](JwkSet).
+#[derive(Debug)]
+pub enum JwksError {
+    /// A key can't be converted to a [decoding key](DecodingKey).
+    Jwk(jsonwebtoken::errors::Error),
+
+    /// Two keys of the set share the same `kid`.
+    DuplicateKid(String),
+
+    /// The set has no key with a `kid`.
+    Empty,
+}
+
+impl fmt::Display for JwksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jwk(e) => write!(f, "invalid key: {e}"),
+            Self::DuplicateKid(kid) => write!(f, "duplicate kid: {kid}"),
+            Self::Empty => write!(f, "the set has no key with a kid"),
+        }
+    }
+}
+
+impl std::error::Error for JwksError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Jwk(e) => Some(e),
+            Self::DuplicateKid(_) | Self::Empty => None,
+        }
+    }
+}
+
+/// Returns the algorithms the key can be used with.
+///
+/// The algorithms are derived from the key type and, for elliptic curve keys,
+/// from the curve, so a P-256 key can't be used with `ES384`. If the key
+/// specifies its algorithm with the `alg` parameter, only this algorithm is
+/// allowed, as long as it suits the key.
+fn algorithms(jwk: &Jwk) -> Vec<Algorithm> {
+    let mut algorithms = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => vec![Algorithm::ES256],
+            EllipticCurve::P384 => vec![Algorithm::ES384],
+            EllipticCurve::P521 | EllipticCurve::Ed25519 => vec![],
+        },
+        AlgorithmParameters::OctetKey(_) => {
+            vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512]
+        }
+        AlgorithmParameters::OctetKeyPair(params) => match params.curve {
+            EllipticCurve::Ed25519 => vec![Algorithm::EdDSA],
+            EllipticCurve::P256 | EllipticCurve::P384 | EllipticCurve::P521 => vec![],
+        },
+    };
+
+    if let Some(alg) = jwk.common.key_algorithm {
+        // Key management algorithms, like `RSA-OAEP`, can't be used to verify
+        // a signature, so such a key matches no algorithm
+        let alg = Algorithm::from_str(&alg.to_string()).ok();
+        algorithms.retain(|&algorithm| Some(algorithm) == alg);
+    }
+
+    algorithms
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        jsonwebtoken::{EncodingKey, Header},
+        serde_json::json,
+    };
+
+    /// A P-256 private key in the PKCS #8 DER format.
+    const EC_KEY: &[u8] = &[
+        0x30, 0x81, 0x87, 0x02, 0x01, 0x00, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+        0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x04, 0x6d, 0x30,
+        0x6b, 0x02, 0x01, 0x01, 0x04, 0x20, 0x63, 0xab, 0x2c, 0xcc, 0xd8, 0x9e, 0x58, 0x19, 0x1c,
+        0xc3, 0xa4, 0x84, 0xaa, 0x0b, 0x33, 0x79, 0x76, 0xce, 0x26, 0x71, 0x0a, 0x3f, 0xca, 0xe2,
+        0x06, 0x05, 0xcc, 0xfc, 0x2f, 0xd7, 0xa4, 0x68, 0xa1, 0x44, 0x03, 0x42, 0x00, 0x04, 0x34,
+        0x0f, 0x06, 0xb9, 0x10, 0x12, 0x1f, 0x34, 0x7f, 0xcc, 0x69, 0x7c, 0xb7, 0xa8, 0x83, 0xa1,
+        0xd4, 0x24, 0xab, 0xe0, 0xf4, 0xde, 0xfa, 0x62, 0xe7, 0xc1, 0xee, 0xe2, 0x2f, 0xda, 0x8b,
+        0xf1, 0xd2, 0xfc, 0xeb, 0x91, 0x9b, 0xf0, 0x4b, 0x87, 0x6a, 0x67, 0x00, 0xfe, 0x14, 0x6f,
+        0x64, 0x8d, 0xd5, 0x3a, 0x27, 0xcb, 0xa8, 0xe6, 0xc6, 0xe8, 0x0f, 0x8c, 0x44, 0x3e, 0x85,
+        0xe8, 0xe2, 0xeb,
+    ];
+
+    fn jwks() -> JwkSet {
+        // The `oct` key is `secret` encoded in base64url
+        let jwks = json!({
+            "keys": [
+                { "kty": "oct", "kid": "hmac", "k": "c2VjcmV0" },
+                { "kty": "oct", "kid": "hs384", "alg": "HS384", "k": "c2VjcmV0" },
+                {
+                    "kty": "EC",
+                    "kid": "ec",
+                    "crv": "P-256",
+                    "x": "NA8GuRASHzR_zGl8t6iDodQkq-D03vpi58Hu4i_ai_E",
+                    "y": "0vzrkZvwS4dqZwD-FG9kjdU6J8uo5sboD4xEPoXo4us",
+                },
+                { "kty": "oct", "k": "b3RoZXI" },
+            ],
+        });
+
+        serde_json::from_value(jwks).expect("the set must be valid")
+    }
+
+    fn validation() -> Validation {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.algorithms = vec![Algorithm::HS256, Algorithm::HS384, Algorithm::ES256];
+        validation.required_spec_claims.clear();
+        validation
+    }
+
+    fn encode(alg: Algorithm, kid: Option<&str>, key: &EncodingKey) -> String {
+        let header = Header {
+            kid: kid.map(str::to_owned),
+            ..Header::new(alg)
+        };
+
+        jsonwebtoken::encode(&header, &json!({ "sub": "alice" }), key).expect("encode the token")
+    }
+
+    fn decode(decoder: &Decoder, token: &str) -> Result<Value, Error> {
+        decoder.decode::<Value>(token).map(|data| data.claims)
+    }
+
+    #[test]
+    fn select_key_by_kid() {
+        let decoder = Decoder::from_jwks(&jwks(), validation()).expect("create the decoder");
+        let hmac = EncodingKey::from_secret(b"secret");
+        let ec = EncodingKey::from_ec_der(EC_KEY);
+
+        let token = encode(Algorithm::HS256, Some("hmac"), &hmac);
+        assert_eq!(
+            decode(&decoder, &token).ok(),
+            Some(json!({ "sub": "alice" })),
+            "hmac"
+        );
+
+        let token = encode(Algorithm::ES256, Some("ec"), &ec);
+        assert_eq!(
+            decode(&decoder, &token).ok(),
+            Some(json!({ "sub": "alice" })),
+            "ec"
+        );
+
+        let token = encode(Algorithm::HS384, Some("hs384"), &hmac);
+        assert!(
+            decode(&decoder, &token).is_ok(),
+            "the key algorithm matches"
+        );
+    }
+
+    #[test]
+    fn reject_other_algorithm() {
+        let decoder = Decoder::from_jwks(&jwks(), validation()).expect("create the decoder");
+        let hmac = EncodingKey::from_secret(b"secret");
+
+        // The key only allows `HS384`
+        let token = encode(Algorithm::HS256, Some("hs384"), &hmac);
+        assert!(
+            matches!(decode(&decoder, &token), Err(Error::Jwt(_))),
+            "the algorithm doesn't match the key",
+        );
+
+        // The EC key can't verify an HMAC signature
+        let token = encode(Algorithm::HS256, Some("ec"), &hmac);
+        assert!(
+            matches!(decode(&decoder, &token), Err(Error::Jwt(_))),
+            "the algorithm doesn't match the key type",
+        );
+    }
+
+    #[test]
+    fn key_algorithms() {
+        let algorithms = |jwk| {
+            let jwk = serde_json::from_value(jwk).expect("the key must be valid");
+            algorithms(&jwk)
+        };
+
+        let ec = |crv| json!({ "kty": "EC", "crv": crv, "x": "AA", "y": "AA" });
+        assert_eq!(algorithms(ec("P-256")), [Algorithm::ES256], "P-256");
+        assert_eq!(algorithms(ec("P-384")), [Algorithm::ES384], "P-384");
+        assert_eq!(algorithms(ec("P-521")), [], "unsupported curve");
+
+        let mut jwk = ec("P-256");
+        jwk["alg"] = json!("ES384");
+        assert_eq!(algorithms(jwk), [], "the algorithm doesn't suit the curve");
+
+        let oct = json!({ "kty": "oct", "alg": "HS512", "k": "c2VjcmV0" });
+        assert_eq!(algorithms(oct), [Algorithm::HS512], "the key algorithm");
+
+        let rsa = json!({ "kty": "RSA", "alg": "RSA-OAEP", "n": "AQAB", "e": "AQAB" });
+        assert_eq!(algorithms(rsa), [], "a key management algorithm");
+    }
+
+    #[test]
+    fn unknown_kid() {
+        let decoder = Decoder::from_jwks(&jwks(), validation()).expect("create the decoder");
+        let other = EncodingKey::from_secret(b"other");
+
+        let token = encode(Algorithm::HS256, Some("unknown"), &other);
+        assert!(
+            matches!(decode(&decoder, &token), Err(Error::UnknownKey)),
+            "the kid is unknown",
+        );
+
+        // The key without `kid` is skipped and can't be selected
+        let token = encode(Algorithm::HS256, None, &other);
+        assert!(
+            matches!(decode(&decoder, &token), Err(Error::UnknownKey)),
+            "the kid is missing",
+        );
+    }
+
+    #[test]
+    fn refresh_on_unknown_kid() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let decoder = Decoder::with_refresh(&jwks(), validation(), {
+            let calls = calls.clone();
+            move || _ = calls.fetch_add(1, Ordering::Relaxed)
+        })
+        .expect("create the decoder");
+
+        let hmac = EncodingKey::from_secret(b"secret");
+        let token = encode(Algorithm::HS256, Some("hmac"), &hmac);
+        assert!(decode(&decoder, &token).is_ok(), "the kid is known");
+        assert_eq!(
+            calls.load(Ordering::Relaxed),
+            0,
+            "no refresh for a known kid"
+        );
+
+        let token = encode(Algorithm::HS256, Some("new"), &hmac);
+        assert!(decode(&decoder, &token).is_err(), "the kid is unknown");
+        assert_eq!(
+            calls.load(Ordering::Relaxed),
+            1,
+            "refresh for an unknown kid"
+        );
+
+        let token = encode(Algorithm::HS256, None, &hmac);
+        assert!(decode(&decoder, &token).is_err(), "the kid is missing");
+        assert_eq!(calls.load(Ordering::Relaxed), 1, "no refresh without a kid");
+    }
+
+    #[test]
+    fn invalid_jwks() {
+        let from_jwks = |keys| {
+            let jwks = serde_json::from_value(json!({ "keys": keys })).expect("the set");
+            Decoder::from_jwks(&jwks, validation())
+        };
+
+        assert!(
+            matches!(from_jwks(json!([])), Err(JwksError::Empty)),
+            "the set is empty",
+        );
+
+        let key = json!({ "kty": "oct", "k": "c2VjcmV0" });
+        assert!(
+            matches!(from_jwks(json!([key])), Err(JwksError::Empty)),
+            "the set has no key with a kid",
+        );
+
+        let key = json!({ "kty": "oct", "kid": "hmac", "k": "c2VjcmV0" });
+        assert!(
+            matches!(
+                from_jwks(json!([key, key])),
+                Err(JwksError::DuplicateKid(kid)) if kid == "hmac",
+            ),
+            "the kid is duplicated",
+        );
+
+        let decoder = Decoder::from_jwks(&jwks(), validation()).expect("create the decoder");
+        assert!(
+            decoder.replace_jwks(&JwkSet { keys: vec![] }).is_err(),
+            "the set is empty",
+        );
+
+        let hmac = EncodingKey::from_secret(b"secret");
+        let token = encode(Algorithm::HS256, Some("hmac"), &hmac);
+        assert!(
+            decode(&decoder, &token).is_ok(),
+            "the keys remain unchanged"
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+        let keys = decoder.keys();
+        let validation = decoder.validation();
+        let replaced = decoder.replace_keys(vec![
+            DecodingKey::from_secret(b"first"),
+            DecodingKey::from_secret(b"second"),
+        ]);
+
+        assert!(replaced, "the keys are replaced");
+        decoder.replace_validation(Validation::new(Algorithm::HS512));
+
+        assert_eq!(keys.len(), 1, "the snapshot keeps the previous keys");
+        assert_eq!(
+            validation.algorithms,
+            [Algorithm::HS256],
+            "the previous validation"
+        );
+        assert_eq!(decoder.keys().len(), 2, "the new keys");
+        assert_eq!(
+            decoder.validation().algorithms,
+            [Algorithm::HS512],
+            "replaced"
+        );
+    }
+
+    #[test]
+    fn reject_empty_keys() {
+        let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+        assert!(!decoder.replace_keys(vec![]), "the empty set is rejected");
+        assert_eq!(decoder.keys().len(), 1, "the keys remain unchanged");
+    }
+
+    #[test]
+    fn copy_settings() {
+        use crate::revoke::Denylist;
+
+        let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+        let clone = decoder.clone();
+        let decoder = decoder
+            .with_revocation(Denylist::new())
+            .with_optional(Optional::Lenient);
+
+        assert!(
+            decoder.verify_now("token").is_none(),
+            "the revocation check is set",
+        );
+
+        assert!(
+            clone.verify_now("token").is_some(),
+            "the earlier clone has no revocation check",
+        );
+
+        assert_eq!(decoder.optional(), Optional::Lenient, "the optional mode");
+        assert_eq!(clone.optional(), Optional::Strict, "the earlier mode");
+        assert!(!clone.is_same(&decoder), "the settings differ");
+        assert!(decoder.is_same(&decoder.clone()), "the same decoder");
+
+        let replaced = decoder.replace_keys(vec![
+            DecodingKey::from_secret(b"first"),
+            DecodingKey::from_secret(b"second"),
+        ]);
+
+        assert!(replaced, "the keys are replaced");
+        assert_eq!(clone.keys().len(), 2, "the keys are shared");
+    }
+
+    #[tokio::test]
+    async fn verify_as() {
+        use {crate::revoke::Denylist, std::time::Duration};
+
+        #[derive(Deserialize)]
+        struct User {
+            sub: String,
+        }
+
+        let mut validation = Validation::default();
+        validation.required_spec_claims.clear();
+
+        let decoder = Decoder::new(DecodingKey::from_secret(b"secret"), validation);
+        let token = encode(Algorithm::HS256, None, &EncodingKey::from_secret(b"secret"));
+        let data = decoder.verify_as::<User>(&token).await;
+        assert_eq!(
+            data.ok().map(|data| data.claims.sub),
+            Some("alice".to_owned()),
+            "the claims are decoded directly",
+        );
+
+        let denylist = Denylist::new();
+        denylist.revoke_subject("alice", Duration::from_secs(60));
+        let decoder = decoder.with_revocation(denylist);
+        assert!(
+            matches!(decoder.verify_as::<User>(&token).await, Err(Error::Revoked)),
+            "the revocation check is applied",
+        );
+    }
+
+    #[tokio::test]
+    async fn revocation_keeps_lenient_claims() {
+        use crate::revoke::Denylist;
+
+        let mut validation = Validation::default();
+        validation.required_spec_claims.clear();
+
+        let decoder = Decoder::new(DecodingKey::from_secret(b"secret"), validation);
+        let key = EncodingKey::from_secret(b"secret");
+        let claims = json!({ "sub": 42, "iat": 1.7e9, "jti": null });
+        let token =
+            jsonwebtoken::encode(&Header::default(), &claims, &key).expect("encode the token");
+
+        decoder
+            .verify(&token)
+            .await
+            .expect("the token is accepted without revocation");
+
+        let decoder = decoder.with_revocation(Denylist::new());
+        decoder
+            .verify(&token)
+            .await
+            .expect("the token is accepted with revocation");
+    }
+
+    #[test]
+    fn try_keys_in_turn() {
+        let keys = vec![
+            DecodingKey::from_secret(b"first"),
+            DecodingKey::from_secret(b"second"),
+        ];
+
+        let mut validation = Validation::default();
+        validation.required_spec_claims.clear();
+        let decoder = Decoder::with_keys(keys, validation).expect("create the decoder");
+
+        let token = encode(Algorithm::HS256, None, &EncodingKey::from_secret(b"second"));
+        assert!(decode(&decoder, &token).is_ok(), "the second key matches");
+
+        let token = encode(Algorithm::HS256, None, &EncodingKey::from_secret(b"third"));
+        assert!(
+            matches!(decode(&decoder, &token), Err(Error::Signature)),
+            "no key matches",
+        );
+    }
 }
@@ -0,0 +1,415 @@
+use {
+    axum_core::extract::FromRef,
+    jsonwebtoken::{EncodingKey, Header},
+    serde::{Serialize, ser::Error as _},
+    serde_json::{Map, Value},
+    std::{
+        error,
+        fmt::{self, Write},
+        ops::Deref,
+        sync::Arc,
+        time::Duration,
+    },
+};
+
+/// An encoder for JSON Web Tokens (JWTs).
+///
+/// The encoder is a counterpart to the [decoder](crate::Decoder). It signs
+/// the claims with the encoding key and stamps the registered claims:
+///
+/// * `iat` and `nbf` are set to the current time.
+/// * `exp` is set to the current time plus the [lifetime](Encoder::with_lifetime).
+/// * `jti` is set to a unique identifier.
+/// * `iss` and `aud` are set if the [issuer](Encoder::with_issuer) and
+///   the [audience](Encoder::with_audience) are configured.
+///
+/// If the claims already contain any of these fields, they are left as is.
+///
+/// # Examples
+///
+/// Like the decoder, the encoder can be obtained from the application
+/// [state]:
+///
+/// [state]: https://docs.rs/axum/latest/axum/struct.Router.html#method.with_state
+///
+/// ```
+/// use {
+///     axum::{Router, extract::State, http::StatusCode, routing},
+///     axum_jwt::{
+///         Decoder, Encoder,
+///         jsonwebtoken::{DecodingKey, EncodingKey},
+///     },
+///     serde::Serialize,
+///     std::{sync::Arc, time::Duration},
+/// };
+///
+/// struct App {
+///     encoder: Encoder,
+///     decoder: Decoder,
+/// }
+///
+/// impl AsRef<Encoder> for App {
+///     fn as_ref(&self) -> &Encoder {
+///         &self.encoder
+///     }
+/// }
+///
+/// impl AsRef<Decoder> for App {
+///     fn as_ref(&self) -> &Decoder {
+///         &self.decoder
+///     }
+/// }
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn login(State(encoder): State<Encoder>) -> Result<String, StatusCode> {
+///     // Check the user's credentials here
+///     let user = User {
+///         sub: "alice".to_owned(),
+///     };
+///
+///     encoder
+///         .encode(&user)
+///         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+/// }
+///
+/// let encoder = Encoder::from_key(EncodingKey::from_secret(b"secret"))
+///     .with_issuer("auth.example.com")
+///     .with_lifetime(Duration::from_secs(15 * 60));
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+///
+/// let app = Router::new()
+///     .route("/login", routing::post(login))
+///     .with_state(Arc::new(App { encoder, decoder }));
+/// # let _: Router = app;
+/// ```
+#[derive(Clone)]
+pub struct Encoder(Arc<Inner>);
+
+impl Encoder {
+    /// Creates an encoder from the provided encoding key.
+    ///
+    /// The default [header](Header) is used, so the key must be suitable
+    /// for the `HS256` algorithm.
+    pub fn from_key(key: EncodingKey) -> Self {
+        Self::new(key, Header::default())
+    }
+
+    /// Creates an encoder from the provided encoding key and header.
+    ///
+    /// The token lifetime is one hour by default.
+    pub fn new(key: EncodingKey, header: Header) -> Self {
+        Self(Arc::new(Inner {
+            key,
+            header,
+            issuer: None,
+            audience: vec![],
+            lifetime: Duration::from_secs(60 * 60),
+        }))
+    }
+
+    /// Sets the `kid` field of the token header.
+    pub fn with_kid<K>(mut self, kid: K) -> Self
+    where
+        K: Into<String>,
+    {
+        Arc::make_mut(&mut self.0).header.kid = Some(kid.into());
+        self
+    }
+
+    /// Sets the `iss` claim.
+    pub fn with_issuer<S>(mut self, issuer: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Arc::make_mut(&mut self.0).issuer = Some(issuer.into());
+        self
+    }
+
+    /// Sets the `aud` claim.
+    ///
+    /// If only one audience is provided, the claim is encoded as a string,
+    /// otherwise as an array of strings.
+    pub fn with_audience<A>(mut self, audience: A) -> Self
+    where
+        A: IntoIterator<Item: Into<String>>,
+    {
+        Arc::make_mut(&mut self.0).audience = audience.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the token lifetime used to compute the `exp` claim.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        Arc::make_mut(&mut self.0).lifetime = lifetime;
+        self
+    }
+
+    /// Returns a reference to the header.
+    pub fn header(&self) -> &Header {
+        &self.0.header
+    }
+
+    /// Returns the issuer.
+    pub fn issuer(&self) -> Option<&str> {
+        self.0.issuer.as_deref()
+    }
+
+    /// Returns the audience.
+    pub fn audience(&self) -> &[String] {
+        &self.0.audience
+    }
+
+    /// Returns the token lifetime.
+    pub fn lifetime(&self) -> Duration {
+        self.0.lifetime
+    }
+
+    /// Encodes the claims into a compact token.
+    ///
+    /// The claims must serialize to a JSON object. If the `jti` claim is
+    /// absent and the system random number generator fails,
+    /// [`EncodeError::Random`] is returned.
+    pub fn encode<T>(&self, claims: &T) -> Result<String, EncodeError>
+    where
+        T: Serialize,
+    {
+        let encoder = &*self.0;
+        let mut claims = to_object(claims).map_err(EncodeError::Jwt)?;
+
+        let now = jsonwebtoken::get_current_timestamp();
+        let exp = now.saturating_add(encoder.lifetime.as_secs());
+        stamp(&mut claims, "iat", now);
+        stamp(&mut claims, "nbf", now);
+        stamp(&mut claims, "exp", exp);
+        if !claims.contains_key("jti") {
+            claims.insert("jti".to_owned(), jti()?.into());
+        }
+
+        if let Some(issuer) = &encoder.issuer {
+            stamp(&mut claims, "iss", issuer.as_str());
+        }
+
+        match &encoder.audience[..] {
+            [] => {}
+            [audience] => stamp(&mut claims, "aud", audience.as_str()),
+            audience => stamp(&mut claims, "aud", audience),
+        }
+
+        jsonwebtoken::encode(&encoder.header, &claims, &encoder.key).map_err(EncodeError::Jwt)
+    }
+}
+
+impl fmt::Debug for Encoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encoder")
+            .field("key", &"..")
+            .field("header", &self.0.header)
+            .field("issuer", &self.0.issuer)
+            .field("audience", &self.0.audience)
+            .field("lifetime", &self.0.lifetime)
+            .finish()
+    }
+}
+
+impl<P> FromRef<P> for Encoder
+where
+    P: Deref<Target: AsRef<Self>>,
+{
+    fn from_ref(p: &P) -> Self {
+        p.as_ref().clone()
+    }
+}
+
+/// Errors that can occur during the token encoding.
+#[derive(Debug)]
+pub enum EncodeError {
+    /// Failed to serialize or sign the claims.
+    Jwt(jsonwebtoken::errors::Error),
+
+    /// The system random number generator failed to generate the `jti`.
+    Random(getrandom::Error),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jwt(e) => write!(f, "{e}"),
+            Self::Random(e) => write!(f, "failed to generate the identifier: {e}"),
+        }
+    }
+}
+
+impl error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Jwt(e) => Some(e),
+            Self::Random(e) => Some(e),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Inner {
+    key: EncodingKey,
+    header: Header,
+    issuer: Option<String>,
+    audience: Vec<String>,
+    lifetime: Duration,
+}
+
+pub(crate) fn to_object<T>(claims: &T) -> Result<Map<String, Value>, jsonwebtoken::errors::Error>
+where
+    T: Serialize,
+{
+    match serde_json::to_value(claims)? {
+        Value::Object(claims) => Ok(claims),
+        _ => Err(serde_json::Error::custom("claims must be serialized to an object").into()),
+    }
+}
+
+fn stamp<V>(claims: &mut Map<String, Value>, name: &str, value: V)
+where
+    V: Into<Value>,
+{
+    if !claims.contains_key(name) {
+        claims.insert(name.to_owned(), value.into());
+    }
+}
+
+/// Generates a unique token identifier.
+///
+/// The identifier is 128 random bits encoded as a hex string. Returns
+/// an error if the system random number generator fails.
+pub(crate) fn jti() -> Result<String, EncodeError> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).map_err(EncodeError::Random)?;
+
+    let jti = bytes.iter().fold(String::with_capacity(32), |mut out, b| {
+        _ = write!(out, "{b:02x}");
+        out
+    });
+
+    Ok(jti)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::Decoder,
+        jsonwebtoken::{DecodingKey, Validation},
+        serde_json::json,
+    };
+
+    fn encoder() -> Encoder {
+        Encoder::from_key(EncodingKey::from_secret(b"secret"))
+    }
+
+    fn claims(token: &str) -> Value {
+        let mut validation = Validation::default();
+        validation.validate_aud = false;
+        let key = DecodingKey::from_secret(b"secret");
+        jsonwebtoken::decode(token, &key, &validation)
+            .expect("decode the token")
+            .claims
+    }
+
+    #[test]
+    fn jti_is_random() {
+        let a = jti().expect("generate the identifier");
+        let b = jti().expect("generate the identifier");
+        assert_eq!(a.len(), 32, "the identifier must be 128 bits in hex");
+        assert!(a.bytes().all(|b| b.is_ascii_hexdigit()), "not a hex: {a}");
+        assert_ne!(a, b, "identifiers must be unique");
+    }
+
+    #[test]
+    fn stamp_claims() {
+        let encoder = encoder()
+            .with_issuer("auth.example.com")
+            .with_audience(["api"])
+            .with_lifetime(Duration::from_secs(60));
+
+        let now = jsonwebtoken::get_current_timestamp();
+        let token = encoder
+            .encode(&json!({ "sub": "alice" }))
+            .expect("encode the token");
+
+        let claims = claims(&token);
+        assert_eq!(claims["sub"], "alice", "the custom claim");
+        assert_eq!(claims["iss"], "auth.example.com", "the issuer");
+        assert_eq!(claims["aud"], "api", "a single audience is a string");
+
+        let iat = claims["iat"].as_u64().expect("the iat claim");
+        assert!((now..now + 5).contains(&iat), "issued now");
+        assert_eq!(claims["nbf"], iat, "valid since issued");
+        assert_eq!(claims["exp"], iat + 60, "the lifetime");
+
+        let jti = claims["jti"].as_str().expect("the jti claim");
+        assert_eq!(jti.len(), 32, "the identifier");
+    }
+
+    #[test]
+    fn keep_claims() {
+        let encoder = encoder()
+            .with_issuer("auth.example.com")
+            .with_audience(["api"]);
+
+        let custom = json!({
+            "iat": 1,
+            "nbf": 2,
+            "exp": 4_000_000_000_u64,
+            "jti": "custom",
+            "iss": "other",
+            "aud": ["a", "b"],
+        });
+
+        let token = encoder.encode(&custom).expect("encode the token");
+        assert_eq!(claims(&token), custom, "the claims are left as is");
+    }
+
+    #[test]
+    fn multiple_audiences() {
+        let token = encoder()
+            .with_audience(["a", "b"])
+            .encode(&json!({}))
+            .expect("encode the token");
+
+        assert_eq!(claims(&token)["aud"], json!(["a", "b"]), "an array");
+
+        let token = encoder().encode(&json!({})).expect("encode the token");
+        assert!(claims(&token).get("aud").is_none(), "no audience");
+    }
+
+    #[test]
+    fn reject_non_object() {
+        assert!(encoder().encode(&"alice").is_err(), "a string");
+        assert!(encoder().encode(&[1, 2]).is_err(), "an array");
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoder = encoder()
+            .with_kid("main")
+            .with_issuer("auth.example.com")
+            .with_audience(["api"]);
+
+        let mut validation = Validation::default();
+        validation.set_issuer(&["auth.example.com"]);
+        validation.set_audience(&["api"]);
+        let decoder = Decoder::new(DecodingKey::from_secret(b"secret"), validation);
+
+        let token = encoder
+            .encode(&json!({ "sub": "alice" }))
+            .expect("encode the token");
+
+        let data = decoder.decode::<Value>(&token).expect("decode the token");
+
+        assert_eq!(data.header.kid.as_deref(), Some("main"), "the header");
+        assert_eq!(data.claims["sub"], "alice", "the claims");
+    }
+}
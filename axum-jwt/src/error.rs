@@ -1,43 +1,209 @@
 use {
+    crate::challenge::{Challenge, Render},
     axum_core::response::{IntoResponse, Response},
-    http::StatusCode,
-    std::convert::Infallible,
+    jsonwebtoken::errors::ErrorKind,
+    std::{convert::Infallible, error, fmt, sync::Arc},
 };
 
 /// Errors that can occur during authentication.
+///
+/// New variants may be added in the future, so matching on the error
+/// requires a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error<U = Infallible> {
-    /// Failed to extract authentication data from the request.
-    Extract,
+    /// The request carries no token.
+    Missing,
 
-    /// JWT error.
+    /// The request carries authentication data, but it's malformed,
+    /// for example, it uses a different authentication scheme.
+    Scheme,
+
+    /// The request carries tokens in more than one place.
+    Ambiguous,
+
+    /// The token has expired.
+    Expired,
+
+    /// The token is not yet valid.
+    NotYetValid,
+
+    /// The token signature is invalid.
+    Signature,
+
+    /// No decoding key matches the `kid` of the token header.
+    UnknownKey,
+
+    /// The token audience is invalid.
+    Audience,
+
+    /// The token issuer is invalid.
+    Issuer,
+
+    /// Other JWT error, for example, the token is malformed.
     Jwt(jsonwebtoken::errors::Error),
 
+    /// The token has been [revoked](crate::revoke).
+    Revoked,
+
+    /// The [introspection](crate::introspect) endpoint reports the token
+    /// as inactive.
+    Inactive,
+
+    /// The token can't be verified, because the verification service is
+    /// unavailable.
+    Unavailable,
+
+    /// The [CSRF](crate::Csrf) check failed.
+    Csrf,
+
+    /// The token is rejected by a filter.
+    Rejected,
+
+    /// The token lacks the scope required to access the resource.
+    InsufficientScope,
+
     /// Custom error.
     Custom(U),
 }
 
 impl<U> Error<U> {
+    /// Maps the custom error with the provided function, keeping any other
+    /// variant as is.
     pub fn map<F, E>(self, f: F) -> Error<E>
     where
         F: FnOnce(U) -> E,
     {
         match self {
-            Self::Extract => Error::Extract,
+            Self::Missing => Error::Missing,
+            Self::Scheme => Error::Scheme,
+            Self::Ambiguous => Error::Ambiguous,
+            Self::Expired => Error::Expired,
+            Self::NotYetValid => Error::NotYetValid,
+            Self::Signature => Error::Signature,
+            Self::UnknownKey => Error::UnknownKey,
+            Self::Audience => Error::Audience,
+            Self::Issuer => Error::Issuer,
             Self::Jwt(e) => Error::Jwt(e),
+            Self::Revoked => Error::Revoked,
+            Self::Inactive => Error::Inactive,
+            Self::Unavailable => Error::Unavailable,
+            Self::Csrf => Error::Csrf,
+            Self::Rejected => Error::Rejected,
+            Self::InsufficientScope => Error::InsufficientScope,
             Self::Custom(u) => Error::Custom(f(u)),
         }
     }
 }
 
+impl Error {
+    /// Converts the error into one with any custom type.
+    pub fn widen<U>(self) -> Error<U> {
+        self.map(|never| match never {})
+    }
+}
+
+/// Classifies the JWT error.
+impl<U> From<jsonwebtoken::errors::Error> for Error<U> {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        match e.kind() {
+            ErrorKind::ExpiredSignature => Self::Expired,
+            ErrorKind::ImmatureSignature => Self::NotYetValid,
+            ErrorKind::InvalidSignature => Self::Signature,
+            ErrorKind::InvalidAudience => Self::Audience,
+            ErrorKind::InvalidIssuer => Self::Issuer,
+            _ => Self::Jwt(e),
+        }
+    }
+}
+
+impl<U> fmt::Display for Error<U>
+where
+    U: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "the token is missing"),
+            Self::Scheme => write!(f, "the authentication scheme is malformed"),
+            Self::Ambiguous => write!(f, "the request carries more than one token"),
+            Self::Expired => write!(f, "the token has expired"),
+            Self::NotYetValid => write!(f, "the token is not yet valid"),
+            Self::Signature => write!(f, "the token signature is invalid"),
+            Self::UnknownKey => write!(f, "the token is signed with an unknown key"),
+            Self::Audience => write!(f, "the token audience is invalid"),
+            Self::Issuer => write!(f, "the token issuer is invalid"),
+            Self::Jwt(e) => write!(f, "invalid token: {e}"),
+            Self::Revoked => write!(f, "the token has been revoked"),
+            Self::Inactive => write!(f, "the token is not active"),
+            Self::Unavailable => write!(f, "the verification service is unavailable"),
+            Self::Csrf => write!(f, "the CSRF check failed"),
+            Self::Rejected => write!(f, "the token is rejected"),
+            Self::InsufficientScope => write!(f, "the token lacks the required scope"),
+            Self::Custom(u) => u.fmt(f),
+        }
+    }
+}
+
+impl<U> error::Error for Error<U>
+where
+    U: fmt::Debug + fmt::Display,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Jwt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Renders the error with the default [challenge](Challenge),
+/// which has no realm and scope.
 impl<U> IntoResponse for Error<U>
 where
     U: IntoResponse,
 {
     fn into_response(self) -> Response {
-        match self {
-            Self::Extract | Self::Jwt(_) => StatusCode::UNAUTHORIZED.into_response(),
-            Self::Custom(u) => u.into_response(),
-        }
+        Challenge::default().render(self)
+    }
+}
+
+/// The rejection of the [`Token`](crate::Token) and
+/// [`Claims`](crate::Claims) extractors.
+///
+/// The rejection is rendered with the [renderer](Render) of
+/// the [decoder](crate::Decoder).
+pub struct Rejection {
+    error: Error,
+    render: Arc<dyn Render>,
+}
+
+impl Rejection {
+    pub(crate) fn new(error: Error, render: Arc<dyn Render>) -> Self {
+        Self { error, render }
+    }
+
+    /// Returns a reference to the error.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Returns the error.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Debug for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rejection")
+            .field("error", &self.error)
+            .field("render", &"..")
+            .finish()
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        self.render.render(self.error.widen())
     }
 }
@@ -1,10 +1,15 @@
 use {
-    crate::{decode::Decoder, error::Error},
-    axum_core::extract::{FromRef, FromRequestParts},
-    http::request::Parts,
+    crate::{
+        decode::Decoder,
+        error::{Error, Rejection},
+        verify::{self, Verifier},
+    },
+    axum_core::extract::{FromRef, FromRequestParts, OptionalFromRequestParts},
+    http::{HeaderMap, Uri, header, request::Parts},
     jsonwebtoken::{Header, TokenData},
     serde::de::{DeserializeOwned, IgnoredAny},
-    std::{any, fmt, marker::PhantomData},
+    serde_json::Value,
+    std::{any, borrow::Cow, fmt, marker::PhantomData, ops::Deref, sync::Arc},
 };
 
 /// JWT [extractor] type.
@@ -53,25 +58,37 @@ use {
 /// # let _: Router = app;
 /// ```
 ///
+/// The extractor can be combined with the [middleware](crate::layer).
+/// If the middleware has already validated the token using the same
+/// verifier, the extractor doesn't verify it again, but only deserializes
+/// the claims.
+///
 /// # Custom token extractor
 ///
 /// By default, the token is extracted from the `Authorization` header using
 /// the `Bearer` scheme. If you want to change this behavior, create a new type
 /// and implement [`Extract`] for it. Then, you can use it by specifying it as
-/// a generic parameter:
+/// a generic parameter. If the request has passed through
+/// the [middleware](crate::layer::JwtLayer::with_extract) with an extractor
+/// of the same type, its configured value is used. Otherwise, the value
+/// is created from the application state with the [`FromState`] trait, which
+/// is implemented for any extractor implementing [`Default`].
 ///
 /// ```
 /// use {
 ///     axum::{Router, http::request::Parts, routing},
-///     axum_jwt::{Decoder, Extract, Token, jsonwebtoken::DecodingKey},
+///     axum_jwt::{Decoder, Error, Extract, Token, jsonwebtoken::DecodingKey},
 ///     serde::Deserialize,
+///     std::borrow::Cow,
 /// };
 ///
+/// #[derive(Default)]
 /// struct Custom;
 ///
 /// impl Extract for Custom {
-///     fn extract(parts: &mut Parts) -> Option<&str> {
-///         parts.headers.get("X-Auth-Token")?.to_str().ok()
+///     fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+///         let value = parts.headers.get("X-Auth-Token").ok_or(Error::Missing)?;
+///         value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
 ///     }
 /// }
 ///
@@ -92,24 +109,39 @@ use {
 ///     .with_state(decoder);
 /// # let _: Router = app;
 /// ```
-pub struct Token<T = IgnoredAny, X = Bearer> {
+///
+/// # Custom verifier
+///
+/// By default, the token is verified with the [decoder](Decoder). To verify
+/// it another way, implement the [`Verifier`] trait and specify the type as
+/// the third generic parameter, like `Token<User, Bearer, Custom>`.
+/// The application state must then contain the verifier instead of
+/// the decoder.
+pub struct Token<T = IgnoredAny, X = Bearer, V = Decoder> {
     pub header: Header,
     pub claims: T,
-    extract: PhantomData<fn() -> X>,
+    marker: PhantomData<fn() -> (X, V)>,
 }
 
-impl<T, X> Token<T, X> {
+impl<T, X, V> Token<T, X, V> {
     /// Creates a new token.
     pub fn new(header: Header, claims: T) -> Self {
         Self {
             header,
             claims,
-            extract: PhantomData,
+            marker: PhantomData,
         }
     }
 }
 
-impl<T, X> Clone for Token<T, X>
+impl<T, X, V> Token<T, X, V> {
+    /// Returns the header and claims of the token.
+    pub fn into_parts(self) -> (Header, T) {
+        (self.header, self.claims)
+    }
+}
+
+impl<T, X, V> Clone for Token<T, X, V>
 where
     T: Clone,
 {
@@ -117,12 +149,12 @@ where
         Self {
             header: self.header.clone(),
             claims: self.claims.clone(),
-            extract: PhantomData,
+            marker: PhantomData,
         }
     }
 }
 
-impl<T, X> fmt::Debug for Token<T, X>
+impl<T, X, V> fmt::Debug for Token<T, X, V>
 where
     T: fmt::Debug,
 {
@@ -135,23 +167,185 @@ where
     }
 }
 
-impl<S, T, X> FromRequestParts<S> for Token<T, X>
+impl<S, T, X, V> FromRequestParts<S> for Token<T, X, V>
 where
-    Decoder: FromRef<S>,
     S: Sync,
     T: DeserializeOwned + Send,
-    X: Extract,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
 {
-    type Rejection = Error;
+    type Rejection = Rejection;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let token = X::extract(parts).ok_or(Error::Extract)?;
-        let decoder = Decoder::from_ref(state);
-        let TokenData { header, claims } = decoder.decode(token).map_err(Error::Jwt)?;
+        let (_, TokenData { header, claims }) = verify::<_, _, X, V>(parts, state).await?;
         Ok(Self::new(header, claims))
     }
 }
 
+impl<S, T, X, V> OptionalFromRequestParts<S> for Token<T, X, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let res = <Self as FromRequestParts<S>>::from_request_parts(parts, state).await;
+        optional::<_, _, V>(res, state)
+    }
+}
+
+/// Extracts the token from the request, then verifies it and deserializes
+/// the claims.
+///
+/// If the [middleware](crate::layer) has already validated the same token
+/// with the same verifier, the token isn't verified again.
+pub(crate) async fn verify<'p, S, T, X, V>(
+    parts: &'p mut Parts,
+    state: &S,
+) -> Result<(Encoded<'p>, TokenData<T>), Rejection>
+where
+    V: Verifier + FromRef<S>,
+    T: DeserializeOwned,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+{
+    let extract = Resolved::<X>::new(parts, state);
+    verify_with::<_, _, _, V>(&extract, parts, state).await
+}
+
+/// Verifies the token extracted with the provided extractor.
+async fn verify_with<'p, S, T, X, V>(
+    extract: &Resolved<X>,
+    parts: &'p mut Parts,
+    state: &S,
+) -> Result<(Encoded<'p>, TokenData<T>), Rejection>
+where
+    V: Verifier + FromRef<S>,
+    T: DeserializeOwned,
+    X: Extract,
+{
+    let verifier = V::from_ref(state);
+
+    // The middleware may have stripped the token from the request, so
+    // the token it has verified with the same extractor is taken as is
+    if let Resolved::Configured(_) = extract
+        && let Some(verified) = parts.extensions.get::<Verified<V>>()
+        && verified.verifier.is_same(&verifier)
+    {
+        let token = verified.token.clone();
+        let data = verify::claims(verified.data()).map_err(|e| verify::rejection(&verifier, e))?;
+        return Ok((Encoded::Verified(token), data));
+    }
+
+    let verified = parts.extensions.get::<Verified<V>>().cloned();
+    let token = extract
+        .extract(parts)
+        .map_err(|e| verify::rejection(&verifier, e))?;
+
+    let data = match verified {
+        Some(verified) if verified.is(&token, &verifier) => verify::claims(verified.data()),
+        _ => verifier.verify_as(&token).await,
+    };
+
+    let data = data.map_err(|e| verify::rejection(&verifier, e))?;
+    Ok((Encoded::Request(token), data))
+}
+
+/// The encoded token, either extracted from the request or taken from
+/// the [middleware](crate::layer) which has verified it.
+pub(crate) enum Encoded<'p> {
+    Request(Cow<'p, str>),
+    Verified(Arc<str>),
+}
+
+impl Encoded<'_> {
+    fn into_owned(self) -> String {
+        match self {
+            Self::Request(token) => token.into_owned(),
+            Self::Verified(token) => String::from(&*token),
+        }
+    }
+}
+
+/// The token validated by the middleware.
+///
+/// It's stored in the request extensions, so the extractors can skip
+/// the validation of the same token.
+#[derive(Clone)]
+pub(crate) struct Verified<V> {
+    token: Arc<str>,
+    data: Arc<TokenData<Value>>,
+    verifier: V,
+}
+
+impl<V> Verified<V>
+where
+    V: Verifier,
+{
+    pub(crate) fn new(token: &str, data: TokenData<Value>, verifier: V) -> Self {
+        Self {
+            token: Arc::from(token),
+            data: Arc::new(data),
+            verifier,
+        }
+    }
+
+    pub(crate) fn data(&self) -> &TokenData<Value> {
+        &self.data
+    }
+
+    fn is(&self, token: &str, verifier: &V) -> bool {
+        *self.token == *token && self.verifier.is_same(verifier)
+    }
+}
+
+/// The token extractor configured in the middleware.
+///
+/// It's stored in the request extensions, so the extractors use the same
+/// configuration as the middleware.
+pub(crate) struct Configured<X>(pub(crate) Arc<X>);
+
+impl<X> Clone for Configured<X> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// The token extractor configured in the middleware or created from
+/// the state.
+enum Resolved<X> {
+    Configured(Arc<X>),
+    Created(X),
+}
+
+impl<X> Resolved<X> {
+    fn new<S>(parts: &Parts, state: &S) -> Self
+    where
+        X: FromState<S> + Send + Sync + 'static,
+    {
+        match parts.extensions.get::<Configured<X>>() {
+            Some(Configured(extract)) => Self::Configured(extract.clone()),
+            None => Self::Created(X::from_state(state)),
+        }
+    }
+}
+
+impl<X> Deref for Resolved<X> {
+    type Target = X;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Configured(extract) => extract,
+            Self::Created(extract) => extract,
+        }
+    }
+}
+
 /// JWT [extractor] type returning only claims.
 ///
 /// [extractor]: https://docs.rs/axum/latest/axum/extract/index.html
@@ -195,6 +389,16 @@ where
 ///     .with_state(decoder);
 /// # let _: Router = app;
 /// ```
+///
+/// The token is extracted from the `Authorization` header and verified with
+/// the [decoder](Decoder). To use a [custom token
+/// extractor](Token#custom-token-extractor) or a [custom
+/// verifier](Token#custom-verifier), use the [`ClaimsWith`] extractor.
+///
+/// The extractor isn't generic over them itself: the type parameters would
+/// need a marker field, which would break the `Claims(c)` pattern used to
+/// destructure it. So it stays a plain tuple struct for the common case,
+/// while [`ClaimsWith`] is the generic form.
 #[derive(Clone, Copy, Debug)]
 pub struct Claims<T>(pub T)
 where
@@ -202,30 +406,1225 @@ where
 
 impl<S, T> FromRequestParts<S> for Claims<T>
 where
-    Decoder: FromRef<S>,
     S: Sync,
     T: DeserializeOwned + Send,
+    Decoder: FromRef<S>,
 {
-    type Rejection = Error;
+    type Rejection = Rejection;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let Token { claims, .. }: Token<_> = Token::from_request_parts(parts, state).await?;
+        let ClaimsWith { claims, .. } =
+            <ClaimsWith<_> as FromRequestParts<_>>::from_request_parts(parts, state).await?;
+
         Ok(Self(claims))
     }
 }
 
-/// Trait for token extraction.
-pub trait Extract {
-    fn extract(parts: &mut Parts) -> Option<&str>;
+impl<S, T> OptionalFromRequestParts<S> for Claims<T>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    Decoder: FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let claims =
+            <ClaimsWith<_> as OptionalFromRequestParts<_>>::from_request_parts(parts, state)
+                .await?;
+
+        Ok(claims.map(|ClaimsWith { claims, .. }| Self(claims)))
+    }
 }
 
-/// The token extraction from a header with `Bearer` authentication scheme.
-pub struct Bearer;
+/// JWT [extractor] type returning only claims, generic over the token
+/// extractor and the verifier.
+///
+/// [extractor]: https://docs.rs/axum/latest/axum/extract/index.html
+///
+/// This is the generic form of the [`Claims`] extractor. Like the [`Token`],
+/// it accepts a [custom token extractor](Token#custom-token-extractor) and
+/// a [custom verifier](Token#custom-verifier) as generic parameters.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::http::request::Parts,
+///     axum_jwt::{ClaimsWith, Error, Extract},
+///     serde::Deserialize,
+///     std::borrow::Cow,
+/// };
+///
+/// #[derive(Default)]
+/// struct Custom;
+///
+/// impl Extract for Custom {
+///     fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+///         let value = parts.headers.get("X-Auth-Token").ok_or(Error::Missing)?;
+///         value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn hello(ClaimsWith { claims, .. }: ClaimsWith<User, Custom>) -> String {
+///     format!("Hello, {}!", claims.sub)
+/// }
+/// ```
+pub struct ClaimsWith<T, X = Bearer, V = Decoder> {
+    pub claims: T,
+    marker: PhantomData<fn() -> (X, V)>,
+}
 
-impl Extract for Bearer {
-    fn extract(parts: &mut Parts) -> Option<&str> {
-        let auth = parts.headers.get("Authorization")?;
-        let token = auth.as_bytes().strip_prefix(b"Bearer ")?;
-        str::from_utf8(token).ok()
+impl<T, X, V> ClaimsWith<T, X, V> {
+    /// Creates new claims.
+    pub fn new(claims: T) -> Self {
+        Self {
+            claims,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the claims.
+    pub fn into_inner(self) -> T {
+        self.claims
+    }
+}
+
+impl<T, X, V> Clone for ClaimsWith<T, X, V>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.claims.clone())
+    }
+}
+
+impl<T, X, V> Copy for ClaimsWith<T, X, V> where T: Copy {}
+
+impl<T, X, V> fmt::Debug for ClaimsWith<T, X, V>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClaimsWith")
+            .field("claims", &self.claims)
+            .field("extract", &any::type_name::<X>())
+            .finish()
+    }
+}
+
+impl<S, T, X, V> FromRequestParts<S> for ClaimsWith<T, X, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let (_, TokenData { claims, .. }) = verify::<_, _, X, V>(parts, state).await?;
+        Ok(Self::new(claims))
+    }
+}
+
+impl<S, T, X, V> OptionalFromRequestParts<S> for ClaimsWith<T, X, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let res = <Self as FromRequestParts<S>>::from_request_parts(parts, state).await;
+        optional::<_, _, V>(res, state)
+    }
+}
+
+/// JWT [extractor] type returning the encoded token together with
+/// the decoded one.
+///
+/// [extractor]: https://docs.rs/axum/latest/axum/extract/index.html
+///
+/// It's useful when the token has to be forwarded downstream, for example,
+/// to another service. The token is validated the same way as by
+/// the [`Token`] extractor.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum_jwt::Raw,
+///     serde::Deserialize,
+/// };
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn forward(Raw { token, claims, .. }: Raw<User>) -> String {
+///     format!("Forward the token of {}: {token}", claims.sub)
+/// }
+/// ```
+pub struct Raw<T = IgnoredAny, X = Bearer, V = Decoder> {
+    pub token: String,
+    pub header: Header,
+    pub claims: T,
+    marker: PhantomData<fn() -> (X, V)>,
+}
+
+impl<T, X, V> Raw<T, X, V> {
+    /// Creates a new raw token.
+    pub fn new(token: String, header: Header, claims: T) -> Self {
+        Self {
+            token,
+            header,
+            claims,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, X, V> Clone for Raw<T, X, V>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            token: self.token.clone(),
+            header: self.header.clone(),
+            claims: self.claims.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, X, V> fmt::Debug for Raw<T, X, V>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Raw")
+            .field("token", &"..")
+            .field("header", &self.header)
+            .field("claims", &self.claims)
+            .field("extract", &any::type_name::<X>())
+            .finish()
+    }
+}
+
+impl<S, T, X, V> FromRequestParts<S> for Raw<T, X, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let (token, TokenData { header, claims }) = verify::<_, _, X, V>(parts, state).await?;
+        Ok(Self::new(token.into_owned(), header, claims))
+    }
+}
+
+impl<S, T, X, V> OptionalFromRequestParts<S> for Raw<T, X, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    X: Extract + FromState<S> + Send + Sync + 'static,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let res = <Self as FromRequestParts<S>>::from_request_parts(parts, state).await;
+        optional::<_, _, V>(res, state)
+    }
+}
+
+/// JWT [extractor] type for WebSocket connections.
+///
+/// [extractor]: https://docs.rs/axum/latest/axum/extract/index.html
+///
+/// The token is extracted from the `Sec-WebSocket-Protocol` header with
+/// the [`Protocol`] extractor and validated the same way as by the [`Token`]
+/// extractor. The browser fails the connection unless the server selects one
+/// of the requested protocols, so the extractor also returns the marker
+/// protocol the handler must echo back. If the middleware is
+/// [configured](crate::layer::JwtLayer::with_extract) with another marker,
+/// it's returned instead of the default one.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{http::header, response::IntoResponse},
+///     axum_jwt::Subprotocol,
+///     serde::Deserialize,
+/// };
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn connect(Subprotocol { protocol, claims, .. }: Subprotocol<User>) -> impl IntoResponse {
+///     // Upgrade the connection here and select the marker protocol
+///     ([(header::SEC_WEBSOCKET_PROTOCOL, protocol.into_owned())], claims.sub)
+/// }
+/// ```
+pub struct Subprotocol<T = IgnoredAny, V = Decoder> {
+    /// The marker protocol to select in the response.
+    pub protocol: Cow<'static, str>,
+    pub header: Header,
+    pub claims: T,
+    marker: PhantomData<fn() -> V>,
+}
+
+impl<T, V> Subprotocol<T, V> {
+    /// Creates a new token with the selected protocol.
+    pub fn new(protocol: Cow<'static, str>, header: Header, claims: T) -> Self {
+        Self {
+            protocol,
+            header,
+            claims,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, V> Clone for Subprotocol<T, V>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            protocol: self.protocol.clone(),
+            header: self.header.clone(),
+            claims: self.claims.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, V> fmt::Debug for Subprotocol<T, V>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subprotocol")
+            .field("protocol", &self.protocol)
+            .field("header", &self.header)
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+
+impl<S, T, V> FromRequestParts<S> for Subprotocol<T, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let extract = Resolved::<Protocol>::new(parts, state);
+        let (_, TokenData { header, claims }) =
+            verify_with::<_, _, _, V>(&extract, parts, state).await?;
+
+        Ok(Self::new(extract.name.clone(), header, claims))
+    }
+}
+
+impl<S, T, V> OptionalFromRequestParts<S> for Subprotocol<T, V>
+where
+    S: Sync,
+    T: DeserializeOwned + Send,
+    V: Verifier + FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let res = <Self as FromRequestParts<S>>::from_request_parts(parts, state).await;
+        optional::<_, _, V>(res, state)
+    }
+}
+
+/// Turns the rejection into `None` if the verifier treats the error as
+/// the absence of a token.
+fn optional<S, T, V>(res: Result<T, Rejection>, state: &S) -> Result<Option<T>, Rejection>
+where
+    V: Verifier + FromRef<S>,
+{
+    match res {
+        Ok(v) => Ok(Some(v)),
+        Err(r) if V::from_ref(state).optional().is_anonymous(r.error()) => Ok(None),
+        Err(r) => Err(r),
+    }
+}
+
+/// The treatment of invalid tokens in optional authentication.
+///
+/// A request without a token is always treated as anonymous. This enum
+/// defines what happens if the token is present, but invalid. See
+/// [`Decoder::with_optional`] for the extractors and
+/// [`JwtLayer::with_optional`](crate::layer::JwtLayer::with_optional) for
+/// the middleware.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Optional {
+    /// The request is rejected.
+    #[default]
+    Strict,
+
+    /// The request is treated as anonymous.
+    ///
+    /// Only the errors of the token itself, like an expired or forged one,
+    /// are downgraded. The request is still rejected if the verifier
+    /// is [unavailable](Error::Unavailable), the token is valid, but
+    /// the request fails a [CSRF](Error::Csrf), [scope](Error::InsufficientScope)
+    /// or [filter](Error::Rejected) check, it carries
+    /// [several tokens](Error::Ambiguous), or with a [custom](Error::Custom)
+    /// error.
+    Lenient,
+}
+
+impl Optional {
+    /// Checks if the error means an anonymous request.
+    pub(crate) fn is_anonymous<U>(self, error: &Error<U>) -> bool {
+        match self {
+            Self::Strict => matches!(error, Error::Missing),
+            Self::Lenient => match error {
+                Error::Missing
+                | Error::Scheme
+                | Error::Expired
+                | Error::NotYetValid
+                | Error::Signature
+                | Error::UnknownKey
+                | Error::Audience
+                | Error::Issuer
+                | Error::Jwt(_)
+                | Error::Revoked
+                | Error::Inactive => true,
+                Error::Ambiguous
+                | Error::Unavailable
+                | Error::Csrf
+                | Error::Rejected
+                | Error::InsufficientScope
+                | Error::Custom(_) => false,
+            },
+        }
+    }
+}
+
+/// Trait for token extraction.
+///
+/// The extractor is a value, so it can carry runtime configuration.
+///
+/// If the request carries no token, the extractor returns [`Error::Missing`].
+/// If the token is present, but can't be extracted, for example, because of
+/// a different authentication scheme, it returns [`Error::Scheme`].
+///
+/// The token is usually borrowed from the request. An extractor that has to
+/// decode it, like [`Query`], returns an owned string instead.
+pub trait Extract {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error>;
+
+    /// Removes the token from the request.
+    ///
+    /// The [middleware](crate::layer) calls this method after the token is
+    /// successfully validated or, in the [optional](crate::Optional) mode,
+    /// ignored, before the request is passed to the inner service.
+    /// By default, it does nothing.
+    fn strip(&self, parts: &mut Parts) {
+        _ = parts;
+    }
+}
+
+/// Trait for creating a token extractor from the application state.
+///
+/// The extractors like [`Token`] use the token extractor configured in
+/// the [middleware](crate::layer::JwtLayer::with_extract), if the request has
+/// passed through it. Otherwise, the token extractor is created with this
+/// trait. It's implemented for any extractor implementing [`Default`],
+/// implement it for your state to take the configuration from there.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{
+///         Router,
+///         http::{HeaderName, request::Parts},
+///         routing,
+///     },
+///     axum_jwt::{ClaimsWith, Decoder, Error, Extract, FromState, jsonwebtoken::DecodingKey},
+///     serde::Deserialize,
+///     std::{borrow::Cow, sync::Arc},
+/// };
+///
+/// struct Custom {
+///     header: HeaderName,
+/// }
+///
+/// impl Extract for Custom {
+///     fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+///         let value = parts.headers.get(&self.header).ok_or(Error::Missing)?;
+///         value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
+///     }
+/// }
+///
+/// struct App {
+///     decoder: Decoder,
+///     header: HeaderName,
+/// }
+///
+/// impl AsRef<Decoder> for App {
+///     fn as_ref(&self) -> &Decoder {
+///         &self.decoder
+///     }
+/// }
+///
+/// impl FromState<Arc<App>> for Custom {
+///     fn from_state(app: &Arc<App>) -> Self {
+///         Self {
+///             header: app.header.clone(),
+///         }
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn hello(ClaimsWith { claims: u, .. }: ClaimsWith<User, Custom>) -> String {
+///     format!("Hello, {}!", u.sub)
+/// }
+///
+/// let app = App {
+///     decoder: Decoder::from_key(DecodingKey::from_secret(b"secret")),
+///     header: HeaderName::from_static("x-auth-token"),
+/// };
+///
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .with_state(Arc::new(app));
+/// # let _: Router = app;
+/// ```
+pub trait FromState<S> {
+    fn from_state(state: &S) -> Self;
+}
+
+impl<S, X> FromState<S> for X
+where
+    X: Extract + Default,
+{
+    fn from_state(_: &S) -> Self {
+        Self::default()
+    }
+}
+
+/// The token extraction from a header with `Bearer` authentication scheme.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bearer;
+
+impl Extract for Bearer {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let auth = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .ok_or(Error::Missing)?;
+
+        let token = auth
+            .as_bytes()
+            .strip_prefix(b"Bearer ")
+            .ok_or(Error::Scheme)?;
+        str::from_utf8(token)
+            .map(Cow::Borrowed)
+            .map_err(|_| Error::Scheme)
+    }
+}
+
+/// The token extraction from a cookie.
+///
+/// This is useful for browser clients which keep the token in an `HttpOnly`
+/// cookie. Since the browser sends the cookie automatically, consider
+/// enabling the [CSRF](crate::Csrf) protection in the middleware.
+///
+/// The default cookie name is `access_token`.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{Router, routing},
+///     axum_jwt::{Cookie, Decoder, jsonwebtoken::DecodingKey},
+/// };
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+///
+/// # async fn hello() {}
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .layer(axum_jwt::layer(decoder).with_extract(Cookie::new("session")));
+/// # let _: Router = app;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Cookie {
+    name: Cow<'static, str>,
+}
+
+impl Cookie {
+    /// Creates the extractor of a cookie with the provided name.
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self { name: name.into() }
+    }
+
+    /// Returns the cookie name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Finds the cookie value in the `Cookie` headers.
+    ///
+    /// An empty value, which is what a cleared cookie is often left with,
+    /// counts as no cookie at all.
+    pub(crate) fn find<'h>(&self, headers: &'h HeaderMap) -> Option<&'h str> {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                if name.trim() != self.name {
+                    return None;
+                }
+
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+
+                (!value.is_empty()).then_some(value)
+            })
+    }
+}
+
+impl Default for Cookie {
+    fn default() -> Self {
+        Self::new("access_token")
+    }
+}
+
+impl Extract for Cookie {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        self.find(&parts.headers)
+            .map(Cow::Borrowed)
+            .ok_or(Error::Missing)
+    }
+}
+
+/// The token extraction from a query parameter.
+///
+/// Browser WebSocket and `EventSource` clients can't set the `Authorization`
+/// header, so the token is often passed in the URI, like
+/// `/events?access_token=...`. The default parameter name is `access_token`.
+/// The value is percent-decoded, if it's encoded.
+///
+/// URIs tend to end up in access logs, so the middleware can
+/// [strip](Query::with_strip) the parameter before the request reaches
+/// the inner services.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{Router, routing},
+///     axum_jwt::{Decoder, Query, jsonwebtoken::DecodingKey},
+/// };
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+///
+/// # async fn events() {}
+/// let app = Router::new()
+///     .route("/events", routing::get(events))
+///     .layer(axum_jwt::layer(decoder).with_extract(Query::default().with_strip(true)));
+/// # let _: Router = app;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Query {
+    name: Cow<'static, str>,
+    strip: bool,
+}
+
+impl Query {
+    /// Creates the extractor of a query parameter with the provided name.
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            strip: false,
+        }
+    }
+
+    /// Sets whether the middleware removes the parameter from the URI.
+    ///
+    /// The extractors like [`ClaimsWith`] behind the middleware still get
+    /// the token it has verified, as long as they use the same extractor
+    /// type and verifier.
+    pub fn with_strip(mut self, strip: bool) -> Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Returns the parameter name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_token(&self, pair: &str) -> bool {
+        pair.split_once('=').map_or(pair, |(name, _)| name) == self.name
+    }
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new("access_token")
+    }
+}
+
+impl Extract for Query {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let value = parts
+            .uri
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter_map(|pair| pair.split_once('='))
+            .find_map(|(name, value)| (name == self.name && !value.is_empty()).then_some(value))
+            .ok_or(Error::Missing)?;
+
+        decode(value).ok_or(Error::Scheme)
+    }
+
+    fn strip(&self, parts: &mut Parts) {
+        let Some(query) = parts.uri.query().filter(|_| self.strip) else {
+            return;
+        };
+
+        let path = parts.uri.path();
+        let query: Vec<_> = query
+            .split('&')
+            .filter(|pair| !self.is_token(pair))
+            .collect();
+        let path_and_query = if query.is_empty() {
+            path.parse()
+        } else {
+            format!("{path}?{}", query.join("&")).parse()
+        };
+
+        let mut uri = parts.uri.clone().into_parts();
+        uri.path_and_query = path_and_query.ok();
+        if let Ok(uri) = Uri::from_parts(uri) {
+            parts.uri = uri;
+        }
+    }
+}
+
+/// Decodes the percent-encoded query value, where `+` stands for a space.
+///
+/// Returns `None` if the decoded value isn't valid UTF-8.
+pub(crate) fn decode(value: &str) -> Option<Cow<'_, str>> {
+    if !value.contains(['%', '+']) {
+        return Some(Cow::Borrowed(value));
+    }
+
+    let hex = |b: u8| match b {
+        b'0'..=b'9' => b - b'0',
+        b'a'..=b'f' => b - b'a' + 10,
+        _ => b - b'A' + 10,
+    };
+
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    loop {
+        rest = match rest {
+            [b'+', tail @ ..] => {
+                decoded.push(b' ');
+                tail
+            }
+            [b'%', h, l, tail @ ..] if h.is_ascii_hexdigit() && l.is_ascii_hexdigit() => {
+                decoded.push(hex(*h) << 4 | hex(*l));
+                tail
+            }
+            [b, tail @ ..] => {
+                decoded.push(*b);
+                tail
+            }
+            [] => break,
+        };
+    }
+
+    String::from_utf8(decoded).ok().map(Cow::Owned)
+}
+
+/// The token extraction from the `Sec-WebSocket-Protocol` header.
+///
+/// Browser WebSocket clients can't set the `Authorization` header, but they
+/// can request subprotocols. The client requests a marker protocol followed
+/// by the token itself, like `new WebSocket(url, ["access_token", token])`,
+/// and the extractor takes the value following the marker. The default
+/// marker is `access_token`.
+///
+/// The browser fails the connection unless the server selects one of
+/// the requested protocols, so the handler must echo the marker back in
+/// the response. The [`Subprotocol`] extractor returns the marker together
+/// with the token.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{Router, http::header, response::IntoResponse, routing},
+///     axum_jwt::{Decoder, Protocol, Subprotocol, jsonwebtoken::DecodingKey},
+///     serde::Deserialize,
+/// };
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn connect(t: Subprotocol<User>) -> impl IntoResponse {
+///     // Upgrade the connection here and select the marker protocol,
+///     // which is `auth` as configured in the middleware
+///     ([(header::SEC_WEBSOCKET_PROTOCOL, t.protocol.into_owned())], t.claims.sub)
+/// }
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+///
+/// let app = Router::new()
+///     .route("/ws", routing::get(connect))
+///     .layer(axum_jwt::layer(decoder.clone()).with_extract(Protocol::new("auth")))
+///     .with_state(decoder);
+/// # let _: Router = app;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Protocol {
+    name: Cow<'static, str>,
+}
+
+impl Protocol {
+    /// Creates the extractor with the provided marker protocol.
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self { name: name.into() }
+    }
+
+    /// Returns the marker protocol, which the handler should select.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::new("access_token")
+    }
+}
+
+impl Extract for Protocol {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let mut protocols = parts
+            .headers
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim);
+
+        protocols
+            .find(|protocol| *protocol == self.name)
+            .ok_or(Error::Missing)?;
+
+        protocols.next().map(Cow::Borrowed).ok_or(Error::Scheme)
+    }
+}
+
+/// The token extraction from the first of several sources.
+///
+/// The sources are tried in order. They are specified as a tuple of
+/// extractors, for example, `First<(Bearer, Cookie)>` accepts the token from
+/// the `Authorization` header and falls back to the cookie. The [`Policy`]
+/// defines what to do if a request carries tokens in more than one place.
+///
+/// Only a source the token is successfully extracted from counts as present.
+/// For example, an `Authorization` header with another scheme doesn't prevent
+/// the fallback to the cookie. If no source has a token, the error of
+/// the first malformed source is returned, or [`Error::Missing`] if there is
+/// none.
+///
+/// When used in the [middleware](crate::layer), all sources are
+/// [stripped](Extract::strip) from the request after the validation.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{Router, routing},
+///     axum_jwt::{Bearer, Cookie, Decoder, First, Policy, jsonwebtoken::DecodingKey},
+/// };
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+/// let extract = First::new((Bearer, Cookie::new("session"))).with_policy(Policy::Reject);
+///
+/// # async fn hello() {}
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .layer(axum_jwt::layer(decoder).with_extract(extract));
+/// # let _: Router = app;
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct First<X> {
+    sources: X,
+    policy: Policy,
+}
+
+impl<X> First<X> {
+    /// Creates the extractor from the provided sources.
+    pub fn new(sources: X) -> Self {
+        Self {
+            sources,
+            policy: Policy::default(),
+        }
+    }
+
+    /// Sets the policy for requests carrying more than one token.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<X> Extract for First<X>
+where
+    X: Sources,
+{
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let sources = self.sources.sources();
+        let sources = sources.as_ref();
+
+        let mut found = None;
+        let mut error = None;
+        for (n, source) in sources.iter().enumerate() {
+            match source.extract(parts).map(drop) {
+                Ok(()) => match self.policy {
+                    Policy::First => {
+                        found = Some(n);
+                        break;
+                    }
+                    Policy::Reject if found.is_some() => return Err(Error::Ambiguous),
+                    Policy::Reject => found = Some(n),
+                },
+                Err(Error::Missing) => {}
+                Err(e) => _ = error.get_or_insert(e),
+            }
+        }
+
+        match found {
+            Some(n) => sources[n].extract(parts),
+            None => Err(error.unwrap_or(Error::Missing)),
+        }
+    }
+
+    fn strip(&self, parts: &mut Parts) {
+        for source in self.sources.sources().as_ref() {
+            source.strip(parts);
+        }
+    }
+}
+
+/// The policy for requests carrying tokens in more than one place.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// The token from the first source wins.
+    #[default]
+    First,
+
+    /// The request is rejected as [ambiguous](Error::Ambiguous).
+    Reject,
+}
+
+/// Trait for a list of token extractors.
+///
+/// It's implemented for tuples of up to eight [extractors](Extract).
+pub trait Sources {
+    fn sources(&self) -> impl AsRef<[&dyn Extract]>;
+}
+
+macro_rules! impl_sources {
+    ($($t:ident $n:tt),+) => {
+        impl<$($t),+> Sources for ($($t,)+)
+        where
+            $($t: Extract),+
+        {
+            fn sources(&self) -> impl AsRef<[&dyn Extract]> {
+                [$(&self.$n as &dyn Extract),+]
+            }
+        }
+    };
+}
+
+impl_sources!(A 0);
+impl_sources!(A 0, B 1);
+impl_sources!(A 0, B 1, C 2);
+impl_sources!(A 0, B 1, C 2, D 3);
+impl_sources!(A 0, B 1, C 2, D 3, E 4);
+impl_sources!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_sources!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_sources!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+    use {super::*, http::Request};
+
+    fn parts(headers: &[(&str, &str)]) -> Parts {
+        let mut req = Request::builder();
+        for &(name, value) in headers {
+            req = req.header(name, value);
+        }
+
+        req.body(()).expect("build the request").into_parts().0
+    }
+
+    #[test]
+    fn decode_query() {
+        assert_eq!(decode("a.b-c_d").as_deref(), Some("a.b-c_d"), "as is");
+        assert!(
+            matches!(decode("a.b"), Some(Cow::Borrowed(_))),
+            "no allocation",
+        );
+
+        assert_eq!(decode("a%2Eb%2ec").as_deref(), Some("a.b.c"), "escapes");
+        assert_eq!(decode("a+b").as_deref(), Some("a b"), "space");
+        assert_eq!(decode("100%").as_deref(), Some("100%"), "incomplete");
+        assert_eq!(decode("%zz").as_deref(), Some("%zz"), "not hex");
+        assert_eq!(decode("%ff"), None, "invalid UTF-8");
+    }
+
+    #[test]
+    fn query_token() {
+        let query = Query::default();
+        let mut p = Request::get("/?a=1&access_token=x%2Ey")
+            .body(())
+            .expect("build the request")
+            .into_parts()
+            .0;
+
+        assert_eq!(
+            query.extract(&mut p).ok().as_deref(),
+            Some("x.y"),
+            "decoded"
+        );
+
+        let mut p = Request::get("/?access_token=%ff")
+            .body(())
+            .expect("build the request")
+            .into_parts()
+            .0;
+
+        assert!(
+            matches!(query.extract(&mut p), Err(Error::Scheme)),
+            "malformed token",
+        );
+    }
+
+    #[test]
+    fn cookie_token() {
+        let cookie = Cookie::default();
+        let mut p = parts(&[("cookie", "theme=dark; access_token=\"x.y\"")]);
+        assert_eq!(
+            cookie.extract(&mut p).ok().as_deref(),
+            Some("x.y"),
+            "quoted value"
+        );
+
+        for value in ["access_token=", "access_token=\"\"", "access_token= ; a=b"] {
+            let mut p = parts(&[("cookie", value)]);
+            assert!(
+                matches!(cookie.extract(&mut p), Err(Error::Missing)),
+                "an empty value in {value:?} is no token",
+            );
+        }
+
+        let mut p = parts(&[("cookie", "access_token=; access_token=x.y")]);
+        assert_eq!(
+            cookie.extract(&mut p).ok().as_deref(),
+            Some("x.y"),
+            "the first non-empty value",
+        );
+    }
+
+    #[test]
+    fn lenient_errors() {
+        let anonymous: [Error; 7] = [
+            Error::Missing,
+            Error::Scheme,
+            Error::Expired,
+            Error::Signature,
+            Error::UnknownKey,
+            Error::Revoked,
+            Error::Inactive,
+        ];
+
+        for error in anonymous {
+            assert!(
+                Optional::Lenient.is_anonymous(&error),
+                "{error} is anonymous",
+            );
+        }
+
+        let rejected: [Error; 5] = [
+            Error::Ambiguous,
+            Error::Unavailable,
+            Error::Csrf,
+            Error::Rejected,
+            Error::InsufficientScope,
+        ];
+
+        for error in rejected {
+            assert!(
+                !Optional::Lenient.is_anonymous(&error),
+                "{error} is rejected"
+            );
+        }
+
+        assert!(
+            !Optional::Lenient.is_anonymous(&Error::Custom("custom")),
+            "a custom error is rejected",
+        );
+
+        let expired: Error = Error::Expired;
+        assert!(
+            !Optional::Strict.is_anonymous(&expired),
+            "strict mode rejects an invalid token",
+        );
+    }
+
+    #[test]
+    fn first_source() {
+        let first = First::new((Bearer, Cookie::default()));
+        let mut p = parts(&[
+            ("authorization", "Bearer header"),
+            ("cookie", "access_token=cookie"),
+        ]);
+
+        assert_eq!(
+            first.extract(&mut p).ok().as_deref(),
+            Some("header"),
+            "the first wins"
+        );
+
+        let mut p = parts(&[("cookie", "access_token=cookie")]);
+        assert_eq!(
+            first.extract(&mut p).ok().as_deref(),
+            Some("cookie"),
+            "the fallback"
+        );
+
+        let mut p = parts(&[]);
+        assert!(
+            matches!(first.extract(&mut p), Err(Error::Missing)),
+            "no token",
+        );
+
+        let first = First::new((Cookie::default(), Bearer));
+        let mut p = parts(&[
+            ("cookie", "access_token="),
+            ("authorization", "Bearer header"),
+        ]);
+
+        assert_eq!(
+            first.extract(&mut p).ok().as_deref(),
+            Some("header"),
+            "the fallback after a cleared cookie",
+        );
+    }
+
+    #[test]
+    fn skip_malformed_source() {
+        let first = First::new((Bearer, Cookie::default()));
+        let mut p = parts(&[
+            ("authorization", "Basic dXNlcjpwYXNz"),
+            ("cookie", "access_token=cookie"),
+        ]);
+
+        assert_eq!(
+            first.extract(&mut p).ok().as_deref(),
+            Some("cookie"),
+            "another scheme doesn't count as a token",
+        );
+
+        let mut p = parts(&[("authorization", "Basic dXNlcjpwYXNz")]);
+        assert!(
+            matches!(first.extract(&mut p), Err(Error::Scheme)),
+            "the error of the malformed source",
+        );
+    }
+
+    #[test]
+    fn reject_ambiguous() {
+        let first = First::new((Bearer, Cookie::default())).with_policy(Policy::Reject);
+        let mut p = parts(&[
+            ("authorization", "Bearer header"),
+            ("cookie", "access_token=cookie"),
+        ]);
+
+        assert!(
+            matches!(first.extract(&mut p), Err(Error::Ambiguous)),
+            "two tokens",
+        );
+
+        let mut p = parts(&[
+            ("authorization", "Basic dXNlcjpwYXNz"),
+            ("cookie", "access_token=cookie"),
+        ]);
+
+        assert_eq!(
+            first.extract(&mut p).ok().as_deref(),
+            Some("cookie"),
+            "a single token"
+        );
     }
 }
@@ -0,0 +1,1189 @@
+//! Token introspection.
+//!
+//! Some clients present opaque access tokens instead of JWTs. Such tokens
+//! can't be decoded, instead, the resource server asks the authorization
+//! server about them using the [RFC 7662] introspection endpoint.
+//! The [`Introspector`] is a [verifier](Verifier) that posts the token to
+//! the endpoint, so it can be used with the extractors and
+//! the [middleware](crate::layer) in place of the [decoder](crate::Decoder).
+//!
+//! [RFC 7662]: https://datatracker.ietf.org/doc/html/rfc7662
+//!
+//! If the endpoint reports the token as inactive, it's rejected with
+//! [`Error::Inactive`]. Otherwise, the introspection response becomes
+//! the token claims, so the `sub`, `scope`, `exp` and other members can be
+//! extracted the same way as from a JWT. Opaque tokens have no header, so
+//! the token header is always the default one. If the endpoint can't be
+//! reached or responds with an error, the token is rejected with
+//! [`Error::Unavailable`] and the cause is [reported](Introspector::with_report).
+//!
+//! An active token is still rejected if its `exp` member is in the past
+//! or its `nbf` member is in the future. If either member isn't
+//! a non-negative integer, the token is rejected with [`Error::Inactive`]. Like the [decoder](Decoder),
+//! the introspector can also check the [audience](Introspector::with_audience)
+//! and the [issuer](Introspector::with_issuer) of the token.
+//!
+//! Active tokens are cached until they expire according to their `exp`
+//! member, so the endpoint is only asked once per token. Tokens without
+//! `exp` aren't cached. The number of cached tokens is
+//! [limited](Introspector::with_cache_size).
+//!
+//! If the clients present both JWTs and opaque tokens, use the [`Fallback`]
+//! verifier, which decodes the former and introspects the latter.
+//!
+//! # Examples
+//!
+//! The way of sending the request is defined by the [`Post`] trait. This
+//! allows you to use any HTTP client or, for example, a local stub in tests.
+//!
+//! ```
+//! use {
+//!     axum::{
+//!         Router,
+//!         http::{HeaderValue, Request, Response, header},
+//!         routing,
+//!     },
+//!     axum_jwt::{Bearer, ClaimsWith, introspect::{Introspector, Post}},
+//!     serde::Deserialize,
+//!     std::convert::Infallible,
+//! };
+//!
+//! struct Client;
+//!
+//! impl Post for Client {
+//!     type Error = Infallible;
+//!
+//!     async fn post(&self, _: Request<String>) -> Result<Response<Vec<u8>>, Self::Error> {
+//!         // Send the request to the authorization server here
+//!         let body = br#"{"active":true,"sub":"alice","scope":"read"}"#;
+//!         Ok(Response::new(body.to_vec()))
+//!     }
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct User {
+//!     sub: String,
+//! }
+//!
+//! async fn hello(ClaimsWith { claims: u, .. }: ClaimsWith<User, Bearer, Introspector>) -> String {
+//!     format!("Hello, {}!", u.sub)
+//! }
+//!
+//! let endpoint = "https://auth.example.com/introspect".parse()?;
+//! let introspector = Introspector::new(endpoint, Client).with_header(
+//!     header::AUTHORIZATION,
+//!     HeaderValue::from_static("Basic Y2xpZW50OnNlY3JldA=="),
+//! );
+//!
+//! let app = Router::new()
+//!     .route("/", routing::get(hello))
+//!     .with_state(introspector);
+//! # let _: Router = app;
+//! # Ok::<_, axum::http::uri::InvalidUri>(())
+//! ```
+
+use {
+    crate::{
+        challenge::{Challenge, Render},
+        decode::Decoder,
+        error::Error,
+        extract::Optional,
+        verify::{self, Verifier},
+    },
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri, header,
+    },
+    jsonwebtoken::{Header, TokenData},
+    serde::de::DeserializeOwned,
+    serde_json::{Map, Value},
+    std::{
+        collections::HashMap,
+        error,
+        fmt::{self, Write},
+        pin::Pin,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+    },
+};
+
+/// Trait for posting the introspection request.
+///
+/// The request is ready to be sent: it has the `POST` method, the endpoint
+/// URI, the headers and the form-encoded body. The implementation returns
+/// the response with the raw body.
+pub trait Post: Send + Sync + 'static {
+    type Error: error::Error + Send + Sync + 'static;
+    fn post(
+        &self,
+        req: Request<String>,
+    ) -> impl Future<Output = Result<Response<Vec<u8>>, Self::Error>> + Send;
+}
+
+/// Errors that can occur when the token is introspected.
+#[derive(Debug)]
+pub enum IntrospectError {
+    /// Failed to post the request.
+    Post(Box<dyn error::Error + Send + Sync>),
+
+    /// The endpoint responded with an unsuccessful status.
+    Status(StatusCode),
+
+    /// The response isn't a JSON object.
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for IntrospectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Post(e) => write!(f, "failed to post the introspection request: {e}"),
+            Self::Status(status) => write!(f, "the introspection endpoint responded with {status}"),
+            Self::Parse(e) => write!(f, "invalid introspection response: {e}"),
+        }
+    }
+}
+
+impl error::Error for IntrospectError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Post(e) => Some(&**e),
+            Self::Status(_) => None,
+            Self::Parse(e) => Some(e),
+        }
+    }
+}
+
+/// The future of a posted request.
+type Posted<'a> =
+    Pin<Box<dyn Future<Output = Result<Response<Vec<u8>>, IntrospectError>> + Send + 'a>>;
+
+/// The callback reporting introspection errors.
+type Report = Arc<dyn Fn(IntrospectError) + Send + Sync>;
+
+/// The object safe counterpart of the [`Post`] trait.
+trait DynPost: Send + Sync {
+    fn post(&self, req: Request<String>) -> Posted<'_>;
+}
+
+impl<P> DynPost for P
+where
+    P: Post,
+{
+    fn post(&self, req: Request<String>) -> Posted<'_> {
+        Box::pin(async move {
+            Post::post(self, req)
+                .await
+                .map_err(|e| IntrospectError::Post(Box::new(e)))
+        })
+    }
+}
+
+/// A verifier of opaque tokens using the introspection endpoint.
+///
+/// See the [module](self) documentation for more details.
+///
+/// The cache is shared between all clones of the introspector, including
+/// the ones with different settings. Like for the [decoder](Decoder),
+/// a setting is only applied to the returned introspector and its later
+/// clones.
+#[derive(Clone)]
+pub struct Introspector {
+    shared: Arc<Shared>,
+    settings: Arc<Settings>,
+    endpoint: Uri,
+}
+
+impl Introspector {
+    /// Creates an introspector posting tokens to the provided endpoint.
+    pub fn new<P>(endpoint: Uri, post: P) -> Self
+    where
+        P: Post,
+    {
+        Self {
+            shared: Arc::new(Shared {
+                post: Box::new(post),
+                cache: Mutex::new(Cache::default()),
+            }),
+            settings: Arc::new(Settings::default()),
+            endpoint,
+        }
+    }
+
+    /// Adds a header to the introspection request.
+    ///
+    /// The authorization server usually requires the resource server to
+    /// authenticate, for example, with the `Authorization` header.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        Arc::make_mut(&mut self.settings)
+            .headers
+            .append(name, value);
+        self
+    }
+
+    /// Sets the accepted audiences.
+    ///
+    /// The `aud` member of the introspection response, either a string or
+    /// an array of strings, must contain any of them. Otherwise, including
+    /// if the member is missing, the token is rejected with
+    /// [`Error::Audience`]. By default, the audience isn't checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum_jwt::introspect::{Introspector, Post},
+    ///     http::{Request, Response},
+    ///     std::convert::Infallible,
+    /// };
+    ///
+    /// struct Client;
+    ///
+    /// impl Post for Client {
+    ///     type Error = Infallible;
+    ///
+    ///     async fn post(&self, _: Request<String>) -> Result<Response<Vec<u8>>, Self::Error> {
+    ///         Ok(Response::new(br#"{"active":false}"#.to_vec()))
+    ///     }
+    /// }
+    ///
+    /// let endpoint = "https://auth.example.com/introspect".parse()?;
+    /// let introspector = Introspector::new(endpoint, Client)
+    ///     .with_audience(["https://api.example.com"])
+    ///     .with_issuer(["https://auth.example.com"]);
+    /// # Ok::<_, http::uri::InvalidUri>(())
+    /// ```
+    pub fn with_audience<I, S>(mut self, audience: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let audience = audience.into_iter().map(S::into).collect();
+        Arc::make_mut(&mut self.settings).audience = Some(audience);
+        self
+    }
+
+    /// Sets the accepted issuers.
+    ///
+    /// The `iss` member of the introspection response must be one of them.
+    /// Otherwise, including if the member is missing, the token is rejected
+    /// with [`Error::Issuer`]. By default, the issuer isn't checked.
+    pub fn with_issuer<I, S>(mut self, issuer: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let issuer = issuer.into_iter().map(S::into).collect();
+        Arc::make_mut(&mut self.settings).issuer = Some(issuer);
+        self
+    }
+
+    /// Sets the [challenge](Challenge) used to render authentication errors.
+    ///
+    /// See [`Decoder::with_challenge`](crate::Decoder::with_challenge).
+    pub fn with_challenge(self, challenge: Challenge) -> Self {
+        self.with_render(challenge)
+    }
+
+    /// Sets the [renderer](Render) of authentication errors.
+    ///
+    /// See [`Decoder::with_render`](crate::Decoder::with_render).
+    pub fn with_render<R>(mut self, render: R) -> Self
+    where
+        R: Render,
+    {
+        Arc::make_mut(&mut self.settings).render = Arc::new(render);
+        self
+    }
+
+    /// Sets how optional extractors treat invalid tokens.
+    ///
+    /// See [`Decoder::with_optional`](crate::Decoder::with_optional).
+    pub fn with_optional(mut self, optional: Optional) -> Self {
+        Arc::make_mut(&mut self.settings).optional = optional;
+        self
+    }
+
+    /// Sets a callback to report introspection errors.
+    ///
+    /// If the endpoint can't be reached or its response is invalid,
+    /// the token is rejected with [`Error::Unavailable`]. The callback is
+    /// called with the cause, for example, to log it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axum_jwt::introspect::{Introspector, Post};
+    ///
+    /// fn introspector<P>(post: P) -> Introspector
+    /// where
+    ///     P: Post,
+    /// {
+    ///     let endpoint = http::Uri::from_static("https://auth.example.com/introspect");
+    ///     Introspector::new(endpoint, post).with_report(|e| eprintln!("{e}"))
+    /// }
+    /// ```
+    pub fn with_report<R>(mut self, report: R) -> Self
+    where
+        R: Fn(IntrospectError) + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.settings).report = Some(Arc::new(report));
+        self
+    }
+
+    /// Sets the maximum number of cached tokens.
+    ///
+    /// When the cache is full, the expired tokens are evicted, and if
+    /// there are none, new tokens aren't cached until the cached ones
+    /// expire. The cache is shared between all clones of the introspector,
+    /// so the size applies to them as well. The default is 10000, zero
+    /// disables the cache.
+    pub fn with_cache_size(self, size: usize) -> Self {
+        self.shared.cache().size = size;
+        self
+    }
+
+    /// Returns the introspection endpoint.
+    pub fn endpoint(&self) -> &Uri {
+        &self.endpoint
+    }
+
+    async fn introspect(&self, token: &str) -> Result<Map<String, Value>, Error> {
+        let mut body = String::from("token=");
+        encode(&mut body, token);
+        body.push_str("&token_type_hint=access_token");
+
+        let mut req = Request::new(body);
+        *req.method_mut() = Method::POST;
+        *req.uri_mut() = self.endpoint.clone();
+        let headers = req.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        headers.extend(self.settings.headers.clone());
+
+        let res = self.shared.post.post(req).await.and_then(|res| {
+            if res.status().is_success() {
+                serde_json::from_slice(res.body()).map_err(IntrospectError::Parse)
+            } else {
+                Err(IntrospectError::Status(res.status()))
+            }
+        });
+
+        let mut claims: Map<String, Value> = match res {
+            Ok(claims) => claims,
+            Err(e) => {
+                if let Some(report) = &self.settings.report {
+                    report(e);
+                }
+
+                return Err(Error::Unavailable);
+            }
+        };
+
+        match claims.remove("active") {
+            Some(Value::Bool(true)) => Ok(claims),
+            _ => Err(Error::Inactive),
+        }
+    }
+
+    /// Checks the time, audience and issuer members of an active token.
+    fn check(&self, claims: &Map<String, Value>, now: u64) -> Result<(), Error> {
+        // A malformed time member isn't ignored, the token is treated
+        // as inactive
+        let time = |name| match claims.get(name) {
+            Some(value) => value.as_u64().map(Some).ok_or(Error::Inactive),
+            None => Ok(None),
+        };
+
+        if time("exp")?.is_some_and(|exp| exp <= now) {
+            return Err(Error::Expired);
+        }
+
+        if time("nbf")?.is_some_and(|nbf| nbf > now) {
+            return Err(Error::NotYetValid);
+        }
+
+        let settings = &self.settings;
+        if let Some(audience) = &settings.audience {
+            let accepted = |aud: &str| audience.iter().any(|accepted| accepted == aud);
+            let valid = match claims.get("aud") {
+                Some(Value::String(aud)) => accepted(aud),
+                Some(Value::Array(auds)) => auds.iter().filter_map(Value::as_str).any(accepted),
+                _ => false,
+            };
+
+            if !valid {
+                return Err(Error::Audience);
+            }
+        }
+
+        if let Some(issuer) = &settings.issuer {
+            let iss = claims.get("iss").and_then(Value::as_str);
+            if !iss.is_some_and(|iss| issuer.iter().any(|accepted| accepted == iss)) {
+                return Err(Error::Issuer);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Verifier for Introspector {
+    async fn verify(&self, token: &str) -> Result<TokenData<Value>, Error> {
+        if let Some(res) = self.verify_now(token) {
+            return res;
+        }
+
+        let now = jsonwebtoken::get_current_timestamp();
+        let claims = self.introspect(token).await?;
+        self.check(&claims, now)?;
+        if let Some(exp) = claims.get("exp").and_then(Value::as_u64) {
+            self.shared.cache().insert(token, exp, claims.clone(), now);
+        }
+
+        Ok(data(claims))
+    }
+
+    /// The cached token is verified immediately.
+    ///
+    /// It's checked again, since the cache is shared with the clones
+    /// which may have other settings.
+    fn verify_now(&self, token: &str) -> Option<Result<TokenData<Value>, Error>> {
+        let now = jsonwebtoken::get_current_timestamp();
+        let claims = self.shared.cache().get(token, now)?;
+        Some(self.check(&claims, now).map(|()| data(claims)))
+    }
+
+    fn render(&self) -> Arc<dyn Render> {
+        self.settings.render.clone()
+    }
+
+    fn optional(&self) -> Optional {
+        self.settings.optional
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+            && Arc::ptr_eq(&self.settings, &other.settings)
+            && self.endpoint == other.endpoint
+    }
+}
+
+impl fmt::Debug for Introspector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let settings = &self.settings;
+        f.debug_struct("Introspector")
+            .field("endpoint", &self.endpoint)
+            .field("post", &"..")
+            .field("audience", &settings.audience)
+            .field("issuer", &settings.issuer)
+            .field("render", &"..")
+            .field("optional", &settings.optional)
+            .field("report", &settings.report.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// A verifier of both JWTs and opaque tokens.
+///
+/// Tokens shaped like a JWT, that is, consisting of three dot-separated
+/// segments, are verified with the [decoder](Decoder), all other tokens are
+/// introspected. This is useful while the authorization server migrates from
+/// opaque tokens to JWTs, or if it issues both kinds to different clients.
+///
+/// Authentication errors are rendered and optional extractors treat invalid
+/// tokens as configured in the decoder.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     axum::{
+///         Router,
+///         http::{Request, Response},
+///         routing,
+///     },
+///     axum_jwt::{
+///         Bearer, ClaimsWith, Decoder,
+///         introspect::{Fallback, Introspector, Post},
+///         jsonwebtoken::DecodingKey,
+///     },
+///     serde::Deserialize,
+///     std::convert::Infallible,
+/// };
+///
+/// struct Client;
+///
+/// impl Post for Client {
+///     type Error = Infallible;
+///
+///     async fn post(&self, _: Request<String>) -> Result<Response<Vec<u8>>, Self::Error> {
+///         // Send the request to the authorization server here
+///         Ok(Response::new(br#"{"active":false}"#.to_vec()))
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn hello(ClaimsWith { claims: u, .. }: ClaimsWith<User, Bearer, Fallback>) -> String {
+///     format!("Hello, {}!", u.sub)
+/// }
+///
+/// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+/// let endpoint = "https://auth.example.com/introspect".parse()?;
+/// let introspector = Introspector::new(endpoint, Client);
+///
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .with_state(Fallback::new(decoder, introspector));
+/// # let _: Router = app;
+/// # Ok::<_, axum::http::uri::InvalidUri>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fallback {
+    decoder: Decoder,
+    introspector: Introspector,
+}
+
+impl Fallback {
+    /// Creates a verifier decoding JWTs and introspecting other tokens.
+    pub fn new(decoder: Decoder, introspector: Introspector) -> Self {
+        Self {
+            decoder,
+            introspector,
+        }
+    }
+
+    /// Returns the decoder.
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Returns the introspector.
+    pub fn introspector(&self) -> &Introspector {
+        &self.introspector
+    }
+
+    /// Checks if the token is shaped like a JWT.
+    fn is_jwt(token: &str) -> bool {
+        token.split('.').count() == 3
+    }
+}
+
+impl Verifier for Fallback {
+    async fn verify(&self, token: &str) -> Result<TokenData<Value>, Error> {
+        if Self::is_jwt(token) {
+            self.decoder.verify(token).await
+        } else {
+            self.introspector.verify(token).await
+        }
+    }
+
+    async fn verify_as<T>(&self, token: &str) -> Result<TokenData<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        if Self::is_jwt(token) {
+            self.decoder.verify_as(token).await
+        } else {
+            verify::claims(&self.introspector.verify(token).await?)
+        }
+    }
+
+    fn verify_now(&self, token: &str) -> Option<Result<TokenData<Value>, Error>> {
+        if Self::is_jwt(token) {
+            self.decoder.verify_now(token)
+        } else {
+            self.introspector.verify_now(token)
+        }
+    }
+
+    fn render(&self) -> Arc<dyn Render> {
+        self.decoder.render()
+    }
+
+    fn optional(&self) -> Optional {
+        self.decoder.optional()
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        self.decoder.is_same(&other.decoder) && self.introspector.is_same(&other.introspector)
+    }
+}
+
+struct Shared {
+    post: Box<dyn DynPost>,
+    cache: Mutex<Cache>,
+}
+
+impl Shared {
+    fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The settings of the introspector, which are copied on write.
+#[derive(Clone)]
+struct Settings {
+    headers: HeaderMap,
+    audience: Option<Arc<[String]>>,
+    issuer: Option<Arc<[String]>>,
+    render: Arc<dyn Render>,
+    optional: Optional,
+    report: Option<Report>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            audience: None,
+            issuer: None,
+            render: Arc::new(Challenge::default()),
+            optional: Optional::Strict,
+            report: None,
+        }
+    }
+}
+
+/// Creates the token data from the introspected claims.
+///
+/// Introspected tokens have no JWT header, so the default one is used.
+fn data(claims: Map<String, Value>) -> TokenData<Value> {
+    TokenData {
+        header: Header::default(),
+        claims: Value::Object(claims),
+    }
+}
+
+/// The cache of active tokens.
+struct Cache {
+    tokens: HashMap<Box<str>, (u64, Map<String, Value>)>,
+    sweep: usize,
+    size: usize,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            tokens: HashMap::new(),
+            sweep: 0,
+            size: Self::SIZE,
+        }
+    }
+}
+
+impl Cache {
+    /// The minimum number of entries before expired ones are evicted.
+    const SWEEP: usize = 64;
+
+    /// The default maximum number of entries.
+    const SIZE: usize = 10000;
+
+    fn get(&self, token: &str, now: u64) -> Option<Map<String, Value>> {
+        self.tokens
+            .get(token)
+            .filter(|(exp, _)| now < *exp)
+            .map(|(_, claims)| claims.clone())
+    }
+
+    fn insert(&mut self, token: &str, exp: u64, claims: Map<String, Value>, now: u64) {
+        if self.tokens.len() >= self.sweep.min(self.size) {
+            self.tokens.retain(|_, (exp, _)| now < *exp);
+            self.sweep = (self.tokens.len() * 2).max(Self::SWEEP);
+        }
+
+        if self.tokens.len() < self.size {
+            self.tokens.insert(Box::from(token), (exp, claims));
+        }
+    }
+}
+
+/// Appends the form-urlencoded value.
+fn encode(out: &mut String, value: &str) {
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'*') {
+            out.push(char::from(b));
+        } else {
+            _ = write!(out, "%{b:02X}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::Encoder,
+        jsonwebtoken::{DecodingKey, EncodingKey},
+        serde_json::json,
+        std::{
+            convert::Infallible,
+            sync::atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    #[derive(Default)]
+    struct Posts {
+        count: AtomicUsize,
+        last: Mutex<Option<Request<String>>>,
+    }
+
+    struct Stub {
+        status: StatusCode,
+        body: String,
+        posts: Arc<Posts>,
+    }
+
+    impl Post for Stub {
+        type Error = Infallible;
+
+        async fn post(&self, req: Request<String>) -> Result<Response<Vec<u8>>, Self::Error> {
+            self.posts.count.fetch_add(1, Ordering::Relaxed);
+            *self.posts.last.lock().expect("lock the request") = Some(req);
+            let mut res = Response::new(self.body.clone().into_bytes());
+            *res.status_mut() = self.status;
+            Ok(res)
+        }
+    }
+
+    fn stub(status: StatusCode, body: Value) -> (Introspector, Arc<Posts>) {
+        let posts = Arc::new(Posts::default());
+        let stub = Stub {
+            status,
+            body: body.to_string(),
+            posts: posts.clone(),
+        };
+
+        let endpoint = Uri::from_static("https://auth.example.com/introspect");
+        (Introspector::new(endpoint, stub), posts)
+    }
+
+    fn now() -> u64 {
+        jsonwebtoken::get_current_timestamp()
+    }
+
+    #[tokio::test]
+    async fn inactive() {
+        let (introspector, _) = stub(StatusCode::OK, json!({ "active": false }));
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Inactive)),
+            "the token is inactive",
+        );
+
+        let (introspector, _) = stub(StatusCode::OK, json!({ "sub": "alice" }));
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Inactive)),
+            "no active member",
+        );
+    }
+
+    #[tokio::test]
+    async fn unavailable() {
+        let body = json!({ "active": true });
+        let (introspector, _) = stub(StatusCode::INTERNAL_SERVER_ERROR, body);
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Unavailable)),
+            "the server error",
+        );
+
+        let (introspector, _) = stub(StatusCode::UNAUTHORIZED, json!({}));
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Unavailable)),
+            "the resource server isn't authenticated",
+        );
+
+        let (introspector, _) = stub(StatusCode::OK, json!("active"));
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Unavailable)),
+            "not an object",
+        );
+    }
+
+    #[tokio::test]
+    async fn report_errors() {
+        struct Down;
+
+        impl Post for Down {
+            type Error = std::io::Error;
+
+            async fn post(&self, _: Request<String>) -> Result<Response<Vec<u8>>, Self::Error> {
+                Err(std::io::Error::other("connection refused"))
+            }
+        }
+
+        let errors = Arc::new(Mutex::new(vec![]));
+        let report = {
+            let errors = errors.clone();
+            move |e: IntrospectError| errors.lock().expect("lock the errors").push(e.to_string())
+        };
+
+        let endpoint = Uri::from_static("https://auth.example.com/introspect");
+        let introspector = Introspector::new(endpoint, Down).with_report(report.clone());
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Unavailable)),
+            "the endpoint is down",
+        );
+
+        let (introspector, _) = stub(StatusCode::SERVICE_UNAVAILABLE, json!({}));
+        let introspector = introspector.with_report(report.clone());
+        _ = introspector.verify("opaque").await;
+
+        let (introspector, _) = stub(StatusCode::OK, json!("active"));
+        let introspector = introspector.with_report(report);
+        _ = introspector.verify("opaque").await;
+
+        let (introspector, _) = stub(StatusCode::OK, json!({ "active": false }));
+        _ = introspector.verify("opaque").await;
+
+        let errors = errors.lock().expect("lock the errors");
+        assert_eq!(errors.len(), 3, "the errors are reported, {errors:?}");
+        assert_eq!(
+            errors[0], "failed to post the introspection request: connection refused",
+            "the post error",
+        );
+
+        assert_eq!(
+            errors[1], "the introspection endpoint responded with 503 Service Unavailable",
+            "the status",
+        );
+
+        assert!(
+            errors[2].starts_with("invalid introspection response: "),
+            "the parse error",
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_size() {
+        let body = json!({ "active": true, "exp": now() + 60 });
+        let (introspector, posts) = stub(StatusCode::OK, body);
+        let introspector = introspector.with_cache_size(2);
+        for token in ["first", "second", "third"] {
+            introspector.verify(token).await.expect("verify the token");
+        }
+
+        assert!(
+            introspector.verify_now("second").is_some(),
+            "the token is cached",
+        );
+
+        assert!(
+            introspector.verify_now("third").is_none(),
+            "the cache is full",
+        );
+
+        introspector
+            .verify("third")
+            .await
+            .expect("verify the token");
+        assert_eq!(
+            posts.count.load(Ordering::Relaxed),
+            4,
+            "the uncached token is posted again",
+        );
+    }
+
+    #[tokio::test]
+    async fn request() {
+        let (introspector, posts) = stub(StatusCode::OK, json!({ "active": true }));
+        let introspector = introspector.with_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic Y2xpZW50OnNlY3JldA=="),
+        );
+
+        let data = introspector
+            .verify("a b+c")
+            .await
+            .expect("verify the token");
+        assert_eq!(data.claims, json!({}), "the active member is removed");
+
+        let req = posts
+            .last
+            .lock()
+            .expect("lock the request")
+            .take()
+            .expect("the request is posted");
+
+        assert_eq!(req.method(), Method::POST, "the method");
+        assert_eq!(req.uri(), introspector.endpoint(), "the endpoint");
+        assert_eq!(
+            req.headers()[header::CONTENT_TYPE],
+            "application/x-www-form-urlencoded",
+            "the content type",
+        );
+
+        assert_eq!(
+            req.headers()[header::AUTHORIZATION],
+            "Basic Y2xpZW50OnNlY3JldA==",
+            "the custom header",
+        );
+
+        assert_eq!(
+            req.body(),
+            "token=a%20b%2Bc&token_type_hint=access_token",
+            "the form",
+        );
+    }
+
+    #[tokio::test]
+    async fn cache_until_exp() {
+        let body = json!({ "active": true, "sub": "alice", "exp": now() + 60 });
+        let (introspector, posts) = stub(StatusCode::OK, body);
+        assert!(
+            introspector.verify_now("opaque").is_none(),
+            "not cached yet"
+        );
+
+        for _ in 0..3 {
+            let data = introspector
+                .verify("opaque")
+                .await
+                .expect("verify the token");
+            assert_eq!(data.claims["sub"], "alice", "the claims");
+        }
+
+        assert_eq!(posts.count.load(Ordering::Relaxed), 1, "posted once");
+        assert!(
+            introspector
+                .verify_now("opaque")
+                .is_some_and(|res| res.is_ok()),
+            "the token is cached",
+        );
+
+        introspector
+            .verify("other")
+            .await
+            .expect("verify the token");
+        assert_eq!(posts.count.load(Ordering::Relaxed), 2, "another token");
+    }
+
+    #[tokio::test]
+    async fn no_cache_without_exp() {
+        let body = json!({ "active": true, "sub": "alice" });
+        let (introspector, posts) = stub(StatusCode::OK, body);
+        for _ in 0..3 {
+            introspector
+                .verify("opaque")
+                .await
+                .expect("verify the token");
+        }
+
+        assert_eq!(posts.count.load(Ordering::Relaxed), 3, "posted every time");
+        assert!(introspector.verify_now("opaque").is_none(), "not cached");
+    }
+
+    #[tokio::test]
+    async fn expired() {
+        let body = json!({ "active": true, "exp": now() - 1 });
+        let (introspector, posts) = stub(StatusCode::OK, body);
+        for _ in 0..2 {
+            assert!(
+                matches!(introspector.verify("opaque").await, Err(Error::Expired)),
+                "the token has expired",
+            );
+        }
+
+        assert_eq!(posts.count.load(Ordering::Relaxed), 2, "not cached");
+
+        for time in [json!(1.7e9), json!(-1), json!("1700000000"), json!(null)] {
+            for name in ["exp", "nbf"] {
+                let body = json!({ "active": true, name: time });
+                let (introspector, _) = stub(StatusCode::OK, body);
+                assert!(
+                    matches!(introspector.verify("opaque").await, Err(Error::Inactive)),
+                    "the malformed {name} {time} is rejected",
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn not_yet_valid() {
+        let body = json!({ "active": true, "nbf": now() + 60, "exp": now() + 120 });
+        let (introspector, _) = stub(StatusCode::OK, body);
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::NotYetValid)),
+            "the token isn't valid yet",
+        );
+
+        assert!(introspector.verify_now("opaque").is_none(), "not cached");
+
+        let body = json!({ "active": true, "nbf": now() });
+        let (introspector, _) = stub(StatusCode::OK, body);
+        introspector
+            .verify("opaque")
+            .await
+            .expect("the token is valid");
+    }
+
+    #[tokio::test]
+    async fn audience() {
+        let exp = now() + 60;
+        for aud in [json!("api"), json!(["web", "api"])] {
+            let body = json!({ "active": true, "aud": aud, "exp": exp });
+            let (introspector, _) = stub(StatusCode::OK, body);
+            let introspector = introspector.with_audience(["api", "admin"]);
+            introspector
+                .verify("opaque")
+                .await
+                .expect("the audience is accepted");
+        }
+
+        for aud in [json!("web"), json!(["web"]), json!(null), json!(1)] {
+            let body = json!({ "active": true, "aud": aud });
+            let (introspector, _) = stub(StatusCode::OK, body);
+            let introspector = introspector.with_audience(["api"]);
+            assert!(
+                matches!(introspector.verify("opaque").await, Err(Error::Audience)),
+                "the audience {aud} is rejected",
+            );
+        }
+
+        let (introspector, _) = stub(StatusCode::OK, json!({ "active": true }));
+        let introspector = introspector.with_audience(["api"]);
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Audience)),
+            "no audience",
+        );
+    }
+
+    #[tokio::test]
+    async fn issuer() {
+        let body = json!({ "active": true, "iss": "https://auth.example.com" });
+        let (introspector, _) = stub(StatusCode::OK, body);
+        let introspector = introspector.with_issuer(["https://auth.example.com"]);
+        introspector
+            .verify("opaque")
+            .await
+            .expect("the issuer is accepted");
+
+        let introspector = introspector.with_issuer(["https://other.example.com"]);
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Issuer)),
+            "another issuer",
+        );
+
+        let (introspector, _) = stub(StatusCode::OK, json!({ "active": true }));
+        let introspector = introspector.with_issuer(["https://auth.example.com"]);
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Issuer)),
+            "no issuer",
+        );
+    }
+
+    #[tokio::test]
+    async fn check_cached() {
+        let body = json!({ "active": true, "aud": "api", "exp": now() + 60 });
+        let (introspector, posts) = stub(StatusCode::OK, body);
+        let clone = introspector.clone();
+        introspector
+            .verify("opaque")
+            .await
+            .expect("verify the token");
+
+        let introspector = introspector.with_audience(["admin"]);
+        assert!(
+            matches!(clone.verify_now("opaque"), Some(Ok(_))),
+            "the earlier clone accepts the cached token",
+        );
+
+        assert!(
+            matches!(introspector.verify("opaque").await, Err(Error::Audience)),
+            "the cached token is rejected",
+        );
+
+        assert_eq!(posts.count.load(Ordering::Relaxed), 1, "posted once");
+    }
+
+    #[tokio::test]
+    async fn copy_settings() {
+        let (introspector, posts) = stub(StatusCode::OK, json!({ "active": true }));
+        let clone = introspector.clone();
+        let introspector = introspector
+            .with_header(header::AUTHORIZATION, HeaderValue::from_static("Basic x"))
+            .with_optional(Optional::Lenient);
+
+        let last = || {
+            posts
+                .last
+                .lock()
+                .expect("lock the request")
+                .take()
+                .expect("the request is posted")
+        };
+
+        introspector
+            .verify("opaque")
+            .await
+            .expect("verify the token");
+        assert_eq!(
+            last().headers()[header::AUTHORIZATION],
+            "Basic x",
+            "the header is set",
+        );
+
+        clone.verify("opaque").await.expect("verify the token");
+        assert!(
+            !last().headers().contains_key(header::AUTHORIZATION),
+            "the earlier clone has no header",
+        );
+
+        assert_eq!(
+            introspector.optional(),
+            Optional::Lenient,
+            "the optional mode"
+        );
+        assert_eq!(clone.optional(), Optional::Strict, "the earlier mode");
+        assert!(!clone.is_same(&introspector), "the settings differ");
+        assert!(
+            introspector.is_same(&introspector.clone()),
+            "the same introspector",
+        );
+    }
+
+    #[test]
+    fn encode_form() {
+        let mut out = String::new();
+        encode(&mut out, "aZ09-._*");
+        assert_eq!(out, "aZ09-._*", "unreserved");
+
+        let mut out = String::new();
+        encode(&mut out, "a b+c/d=e&f~\u{e4}");
+        assert_eq!(out, "a%20b%2Bc%2Fd%3De%26f%7E%C3%A4", "reserved");
+    }
+
+    #[tokio::test]
+    async fn fallback() {
+        let (introspector, posts) = stub(
+            StatusCode::OK,
+            json!({ "active": true, "sub": "opaque", "exp": now() + 60 }),
+        );
+
+        let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+        let fallback = Fallback::new(decoder, introspector);
+
+        let encoder = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+        let token = encoder
+            .encode(&json!({ "sub": "jwt" }))
+            .expect("encode the token");
+
+        let data = fallback.verify(&token).await.expect("verify the JWT");
+        assert_eq!(data.claims["sub"], "jwt", "the token is decoded");
+        assert_eq!(posts.count.load(Ordering::Relaxed), 0, "not introspected");
+
+        let data = fallback.verify("opaque").await.expect("verify the token");
+        assert_eq!(data.claims["sub"], "opaque", "the token is introspected");
+        assert_eq!(posts.count.load(Ordering::Relaxed), 1, "introspected");
+
+        assert!(
+            matches!(fallback.verify("a.b.c").await, Err(Error::Jwt(_))),
+            "the malformed JWT isn't introspected",
+        );
+
+        assert_eq!(posts.count.load(Ordering::Relaxed), 1, "still once");
+    }
+}
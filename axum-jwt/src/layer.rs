@@ -42,28 +42,43 @@
 //! # Configuration
 //!
 //! The [`layer`] function accepts a [decoder](Decoder) that defines how to
-//! decode and validate the token.
+//! decode and validate the token, or any other [verifier](Verifier).
 //!
 //! Additionally, the layer itself can be
 //! configured: set a [filter](JwtLayer::with_filter) to define the token's
-//! data type and perform extra checks, store the token in
+//! data type and perform extra checks, a
+//! [request filter](JwtLayer::with_request_filter) to compare the token with
+//! the request, or an [async filter](JwtLayer::with_async_filter) if
+//! the checks need to do I/O, declare the required scopes or roles with
+//! a [requirement](JwtLayer::with_require), store the token in
 //! [extensions](JwtLayer::store_to_extension) so it can later be retrieved in
-//! the handler via an extractor, or specify a custom
-//! method of [extracting](JwtLayer::with_extract) the token from the request.
+//! the handler via an extractor, specify a custom
+//! method of [extracting](JwtLayer::with_extract) the token from the request,
+//! or let anonymous requests through in the [optional](JwtLayer::with_optional)
+//! mode.
 
 use {
     crate::{
+        authz::Require,
+        csrf::Csrf,
         decode::Decoder,
         error::Error,
-        extract::{Bearer, Extract, Token},
+        extract::{Bearer, Configured, Extract, Optional, Token, Verified},
+        layer::{
+            filter::{Compose, Filter, Filtered},
+            store::{Store, WithToken},
+        },
+        verify::{self, Verifier},
     },
     axum_core::{
+        body::Body,
         extract::Request,
         response::{IntoResponse, Response},
     },
-    http::{Extensions, StatusCode},
+    http::{Extensions, request::Parts},
     jsonwebtoken::TokenData,
     serde::de::{DeserializeOwned, IgnoredAny},
+    serde_json::Value,
     std::{
         any,
         convert::Infallible,
@@ -71,12 +86,26 @@ use {
         marker::PhantomData,
         mem,
         pin::Pin,
+        sync::Arc,
         task::{self, Context, Poll},
     },
     tower_layer::Layer,
     tower_service::Service,
 };
 
+#[cfg(feature = "routes")]
+use {
+    crate::routes::{Access, Routes},
+    axum::extract::MatchedPath,
+};
+
+/// The access policy of a route.
+///
+/// Without the `routes` feature, the layer has no routes, so there are
+/// no policies.
+#[cfg(not(feature = "routes"))]
+enum Access {}
+
 /// Creates a [layer](JwtLayer) for middleware.
 ///
 /// # Examples
@@ -99,12 +128,20 @@ use {
 ///     .layer(axum_jwt::layer(decoder));
 /// # let _: Router = app;
 /// ```
-pub fn layer(decoder: Decoder) -> JwtLayer {
+pub fn layer<V>(verifier: V) -> JwtLayer<IgnoredAny, Discard, Bearer, Discard, V>
+where
+    V: Verifier,
+{
     JwtLayer {
-        decoder,
+        verifier,
         validate: Discard,
-        store: |_, _| {},
-        extract: PhantomData,
+        store: Discard,
+        extract: Arc::new(Bearer),
+        csrf: None,
+        #[cfg(feature = "routes")]
+        routes: None,
+        optional: None,
+        claims: PhantomData,
     }
 }
 
@@ -112,14 +149,25 @@ pub fn layer(decoder: Decoder) -> JwtLayer {
 ///
 /// To configure the layer and create the middleware service, call
 /// the [`layer`] function.
-pub struct JwtLayer<I = IgnoredAny, H = Discard, X = Bearer> {
-    decoder: Decoder,
+///
+/// The configuration methods can be called in any order. The type
+/// parameters track the claims type, the [filter](JwtLayer::with_filter),
+/// the [extractor](JwtLayer::with_extract) and what to
+/// [store](JwtLayer::store_to_extension) in the extensions. They're checked
+/// against each other once the layer is applied to a router.
+pub struct JwtLayer<I = IgnoredAny, H = Discard, X = Bearer, St = Discard, V = Decoder> {
+    verifier: V,
     validate: H,
-    store: fn(Token<I>, &mut Extensions),
-    extract: PhantomData<fn() -> X>,
+    store: St,
+    extract: Arc<X>,
+    csrf: Option<Arc<Csrf>>,
+    #[cfg(feature = "routes")]
+    routes: Option<Arc<Routes>>,
+    optional: Option<Optional>,
+    claims: PhantomData<fn() -> I>,
 }
 
-impl<I, X> JwtLayer<I, Discard, X> {
+impl<I, H, X, St, V> JwtLayer<I, H, X, St, V> {
     /// Sets a filter for additional validation.
     ///
     /// By default, the layer only validates the token header, ignoring all
@@ -128,6 +176,10 @@ impl<I, X> JwtLayer<I, Discard, X> {
     ///
     /// The claims type must implement [`Deserialize`](serde::Deserialize).
     ///
+    /// If a filter is already set, the new one is combined with it, so
+    /// the token must pass [both](And) of them. Both filters must accept
+    /// the same claims type.
+    ///
     /// # Examples
     ///
     /// ```
@@ -173,22 +225,188 @@ impl<I, X> JwtLayer<I, Discard, X> {
     ///   and `Err(e)` means it failed. The error type must implement
     ///   [`IntoResponse`], which will be called on failure to return the
     ///   corresponding response.
-    pub fn with_filter<H, N, O>(self, validate: H) -> JwtLayer<N, H, X>
+    pub fn with_filter<G, N, O>(self, validate: G) -> JwtLayer<N, H::Output, X, St, V>
+    where
+        H: Compose<N, G>,
+        G: FnMut(&Token<N>) -> O,
+        N: DeserializeOwned,
+        O: Output,
+    {
+        self.filter(validate)
+    }
+
+    /// Sets a filter for additional validation with access to the request.
+    ///
+    /// This is the same as [`with_filter`](JwtLayer::with_filter), but
+    /// the callback also receives the request [parts](Parts). This allows you
+    /// to cross-check the token claims with the request path, method, query
+    /// or headers.
+    ///
+    /// # Examples
+    ///
+    /// Check that the `tenant` claim matches the `/tenants/{id}` path segment:
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, http::{StatusCode, request::Parts}, routing},
+    ///     axum_jwt::{Decoder, Token, jsonwebtoken::DecodingKey},
+    ///     serde::Deserialize,
+    /// };
+    ///
+    /// #[derive(Deserialize)]
+    /// struct User {
+    ///     tenant: String,
+    /// }
+    ///
+    /// fn check_tenant(t: &Token<User>, parts: &Parts) -> Result<(), StatusCode> {
+    ///     let tenant = parts
+    ///         .uri
+    ///         .path()
+    ///         .strip_prefix("/tenants/")
+    ///         .and_then(|rest| rest.split('/').next());
+    ///
+    ///     if tenant == Some(&t.claims.tenant) {
+    ///         Ok(())
+    ///     } else {
+    ///         Err(StatusCode::FORBIDDEN)
+    ///     }
+    /// }
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// # async fn hello() {}
+    /// let app = Router::new()
+    ///     .route("/tenants/{id}", routing::get(hello))
+    ///     .layer(axum_jwt::layer(decoder).with_request_filter(check_tenant));
+    /// # let _: Router = app;
+    /// ```
+    pub fn with_request_filter<G, N, O>(self, validate: G) -> JwtLayer<N, H::Output, X, St, V>
     where
-        H: FnMut(&Token<N>) -> O,
+        H: Compose<N, RequestFilter<G>>,
+        G: FnMut(&Token<N>, &Parts) -> O,
         N: DeserializeOwned,
         O: Output,
+    {
+        self.filter(RequestFilter(validate))
+    }
+
+    /// Sets an asynchronous filter for additional validation.
+    ///
+    /// This is the same as [`with_filter`](JwtLayer::with_filter), but
+    /// the callback returns a future, so the filter can, for example, look up
+    /// the user in a database or call a permission service. The future is
+    /// awaited by the middleware before the inner service is called.
+    ///
+    /// The callback receives a reference to the token, but the returned future
+    /// must be `'static`, so copy the data you need from the token before
+    /// the `async` block. The output of the future has the same
+    /// [meaning](JwtLayer::with_filter#callback-return-value) as the return
+    /// value of a synchronous filter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, http::StatusCode, routing},
+    ///     axum_jwt::{Decoder, Token, jsonwebtoken::DecodingKey},
+    ///     serde::Deserialize,
+    ///     std::sync::Arc,
+    /// };
+    ///
+    /// struct Db;
+    ///
+    /// impl Db {
+    ///     async fn is_banned(&self, user: &str) -> bool {
+    ///         // Query the database here
+    ///         false
+    ///     }
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct User {
+    ///     sub: String,
+    /// }
+    ///
+    /// let db = Arc::new(Db);
+    ///
+    /// // Checks that the user isn't banned.
+    /// let check_access = move |t: &Token<User>| {
+    ///     let db = db.clone();
+    ///     let user = t.claims.sub.clone();
+    ///     async move {
+    ///         if db.is_banned(&user).await {
+    ///             Err(StatusCode::FORBIDDEN)
+    ///         } else {
+    ///             Ok(())
+    ///         }
+    ///     }
+    /// };
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// # async fn hello() {}
+    /// let app = Router::new()
+    ///     .route("/", routing::get(hello))
+    ///     .layer(axum_jwt::layer(decoder).with_async_filter(check_access));
+    /// # let _: Router = app;
+    /// ```
+    pub fn with_async_filter<G, N, F>(self, validate: G) -> JwtLayer<N, H::Output, X, St, V>
+    where
+        H: Compose<N, AsyncFilter<G>>,
+        G: FnMut(&Token<N>) -> F,
+        N: DeserializeOwned,
+        F: Future<Output: Output> + Send + 'static,
+    {
+        self.filter(AsyncFilter(validate))
+    }
+
+    /// Sets an authorization [requirement](crate::authz).
+    ///
+    /// The requirement is checked against the token claims, regardless of
+    /// the claims type of the layer. If it isn't satisfied, the request is
+    /// rejected with [`Error::InsufficientScope`]. Like any other filter,
+    /// the requirement is combined with the already set ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, routing},
+    ///     axum_jwt::{Decoder, authz, jsonwebtoken::DecodingKey},
+    /// };
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// # async fn orders() {}
+    /// let app = Router::new()
+    ///     .route("/orders", routing::get(orders))
+    ///     .layer(axum_jwt::layer(decoder).with_require(authz::require_scopes(["read:orders"])));
+    /// # let _: Router = app;
+    /// ```
+    pub fn with_require(self, require: Require) -> JwtLayer<I, H::Output, X, St, V>
+    where
+        H: Compose<I, Require>,
+    {
+        self.filter(require)
+    }
+
+    fn filter<N, G>(self, validate: G) -> JwtLayer<N, H::Output, X, St, V>
+    where
+        H: Compose<N, G>,
     {
         JwtLayer {
-            decoder: self.decoder,
-            validate,
-            store: |_, _| {},
-            extract: PhantomData,
+            verifier: self.verifier,
+            validate: self.validate.compose(validate),
+            store: self.store,
+            extract: self.extract,
+            csrf: self.csrf,
+            #[cfg(feature = "routes")]
+            routes: self.routes,
+            optional: self.optional,
+            claims: PhantomData,
         }
     }
-}
 
-impl<I, H, X> JwtLayer<I, H, X> {
     /// Configures the layer to store the token in the [extension].
     ///
     /// [extension]: https://docs.rs/axum/latest/axum/struct.Extension.html
@@ -248,92 +466,285 @@ impl<I, H, X> JwtLayer<I, H, X> {
     /// # let _: Router = app;
     /// ```
     ///
-    /// <section class="warning">
+    /// The order of the calls doesn't matter, the token is stored with
+    /// the claims type of the filter, even if `store_to_extension` is called
+    /// before `with_filter`.
     ///
-    /// Note that the `store_to_extension` call comes after setting
-    /// the `with_filter` filter.
+    /// # Read header only
     ///
-    /// This is important because
-    /// `store_to_extension` applies to the current token type.
-    /// The `with_filter` call may change the type and therefore it always
-    /// resets the `store_to_extension` configuration.
+    /// If you just need to retrieve the token without any additional payload,
+    /// omit the `with_filter` call and use the `Token` type without
+    /// a parameter:
     ///
     /// ```
-    /// # use {
-    /// #     axum::{Router, routing},
-    /// #     axum_jwt::{Decoder, Token, jsonwebtoken::DecodingKey},
-    /// # };
-    /// # fn check_access(t: &Token) -> bool { true }
-    /// # async fn hello() {}
-    /// # let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    /// use {
+    ///     axum::{Extension, Router, routing},
+    ///     axum_jwt::{Decoder, Token, jsonwebtoken::DecodingKey},
+    /// };
+    ///
+    /// async fn hello(Extension(t): Extension<Token>) -> String {
+    ///     // Access the parsed token
+    ///     format!("Decoded with {:?} algorithm", t.header.alg)
+    /// }
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// let app = Router::new()
+    ///     .route("/", routing::get(hello))
+    ///     .layer(axum_jwt::layer(decoder).store_to_extension());
+    /// # let _: Router = app;
+    /// ```
+    pub fn store_to_extension(self) -> JwtLayer<I, H, X, St::Output, V>
+    where
+        St: WithToken,
+    {
+        JwtLayer {
+            verifier: self.verifier,
+            validate: self.validate,
+            store: self.store.with_token(),
+            extract: self.extract,
+            csrf: self.csrf,
+            #[cfg(feature = "routes")]
+            routes: self.routes,
+            optional: self.optional,
+            claims: PhantomData,
+        }
+    }
+
+    /// Configures the layer to store a value derived from the token in
+    /// the [extension].
+    ///
+    /// [extension]: https://docs.rs/axum/latest/axum/struct.Extension.html
+    ///
+    /// The callback converts the validated token into any value, for example,
+    /// the current user or the tenant identifier, which is then stored
+    /// in the extensions. This way, handlers don't depend on the shape of
+    /// the token claims.
+    ///
+    /// The value is stored in addition to the token, if
+    /// [`store_to_extension`](JwtLayer::store_to_extension) is also called,
+    /// or instead of it otherwise. The method can be called multiple times
+    /// to store several values. If two callbacks return the same type,
+    /// the value of the last call is stored. Like the token, the value is
+    /// stored only after successful validation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Extension, Router, routing},
+    ///     axum_jwt::{Decoder, Token, jsonwebtoken::DecodingKey},
+    ///     serde::Deserialize,
+    /// };
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Claims {
+    ///     sub: String,
+    ///     name: String,
+    /// }
+    ///
+    /// #[derive(Clone)]
+    /// struct CurrentUser {
+    ///     id: String,
+    ///     name: String,
+    /// }
+    ///
+    /// fn check_access(_: &Token<Claims>) -> bool {
+    ///     true
+    /// }
+    ///
+    /// async fn hello(Extension(user): Extension<CurrentUser>) -> String {
+    ///     format!("Hello, {}!", user.name)
+    /// }
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
     /// let app = Router::new()
     ///     .route("/", routing::get(hello))
     ///     .layer(
     ///         axum_jwt::layer(decoder)
-    ///             // Incorrect order!
-    ///             .store_to_extension()
+    ///             .store_with::<Claims, _, _>(|t| CurrentUser {
+    ///                 id: t.claims.sub.clone(),
+    ///                 name: t.claims.name.clone(),
+    ///             })
     ///             .with_filter(check_access),
     ///     );
     /// # let _: Router = app;
     /// ```
     ///
-    /// </section>
+    /// The claims type of the callback is resolved when the layer is
+    /// applied and must match the claims type of the filter. Specify it
+    /// explicitly, either as the first generic parameter or as the type of
+    /// the callback argument.
+    pub fn store_with<C, F, T>(self, map: F) -> JwtLayer<I, H, X, StoreWith<F, St>, V>
+    where
+        F: Fn(&Token<C>) -> T,
+        T: Clone + Send + Sync + 'static,
+    {
+        JwtLayer {
+            verifier: self.verifier,
+            validate: self.validate,
+            store: StoreWith {
+                map,
+                next: self.store,
+            },
+            extract: self.extract,
+            csrf: self.csrf,
+            #[cfg(feature = "routes")]
+            routes: self.routes,
+            optional: self.optional,
+            claims: PhantomData,
+        }
+    }
+
+    /// Enables the [CSRF](Csrf) protection.
     ///
-    /// # Read header only
+    /// For requests with unsafe methods, the middleware compares the CSRF
+    /// header with the expected value and rejects the request with
+    /// [`Error::Csrf`] if they don't match. This is needed when the token
+    /// is sent automatically by the browser, for example,
+    /// in a [cookie](crate::Cookie).
     ///
-    /// If you just need to retrieve the token without any additional payload,
-    /// omit the `with_filter` call and use the `Token` type without
-    /// a parameter:
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, routing},
+    ///     axum_jwt::{Cookie, Csrf, Decoder, jsonwebtoken::DecodingKey},
+    /// };
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// # async fn update() {}
+    /// let app = Router::new()
+    ///     .route("/", routing::post(update))
+    ///     .layer(
+    ///         axum_jwt::layer(decoder)
+    ///             .with_extract(Cookie::new("session"))
+    ///             .with_csrf(Csrf::claim("csrf")),
+    ///     );
+    /// # let _: Router = app;
+    /// ```
+    pub fn with_csrf(mut self, csrf: Csrf) -> Self {
+        self.csrf = Some(Arc::new(csrf));
+        self
+    }
+
+    /// Enables the optional authentication.
+    ///
+    /// By default, the middleware rejects requests without a valid token.
+    /// In the optional mode, requests without a token are passed to the inner
+    /// service as anonymous ones. If the token is present, it's validated
+    /// as usual and, if [configured](JwtLayer::store_to_extension), stored
+    /// in the extensions, so the handler can tell an anonymous request
+    /// by the absence of the token.
+    ///
+    /// The `optional` parameter defines what happens if the token is present,
+    /// but invalid: with [`Optional::Strict`] the request is rejected, with
+    /// [`Optional::Lenient`] it's treated as anonymous. The [filter],
+    /// the [CSRF](JwtLayer::with_csrf) check and an unavailable verifier
+    /// reject requests in both modes.
+    ///
+    /// If the layer also has [routes](JwtLayer::with_routes), only
+    /// the routes with the [`Access::Optional`](crate::routes::Access::Optional) policy accept anonymous
+    /// requests. The routes requiring authentication always reject them.
+    ///
+    /// [filter]: JwtLayer::with_filter
+    ///
+    /// # Examples
     ///
     /// ```
     /// use {
     ///     axum::{Extension, Router, routing},
-    ///     axum_jwt::{Decoder, Token, jsonwebtoken::DecodingKey},
+    ///     axum_jwt::{Decoder, Optional, Token, jsonwebtoken::DecodingKey},
     /// };
     ///
-    /// async fn hello(Extension(t): Extension<Token>) -> String {
-    ///     // Access the parsed token
-    ///     format!("Decoded with {:?} algorithm", t.header.alg)
+    /// async fn hello(t: Option<Extension<Token>>) -> String {
+    ///     match t {
+    ///         Some(_) => "Hello, User!".to_owned(),
+    ///         None => "Hello, Anonimus!".to_owned(),
+    ///     }
     /// }
     ///
     /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
     ///
     /// let app = Router::new()
     ///     .route("/", routing::get(hello))
-    ///     .layer(axum_jwt::layer(decoder).store_to_extension());
+    ///     .layer(
+    ///         axum_jwt::layer(decoder)
+    ///             .store_to_extension()
+    ///             .with_optional(Optional::Strict),
+    ///     );
     /// # let _: Router = app;
     /// ```
-    pub fn store_to_extension(mut self) -> Self
-    where
-        I: Clone + Send + Sync + 'static,
-    {
-        self.store = |claims, extensions| {
-            extensions.insert(claims);
-        };
+    pub fn with_optional(mut self, optional: Optional) -> Self {
+        self.optional = Some(optional);
+        self
+    }
 
+    /// Sets the per-route access [policies](crate::routes).
+    ///
+    /// This method requires the `routes` feature.
+    ///
+    /// Requests to public routes are passed to the inner service without
+    /// authentication. For the routes with an authorization requirement,
+    /// it's checked in addition to the [filter](JwtLayer::with_filter).
+    /// Anonymous requests are passed only to the routes with
+    /// the [`Access::Optional`](crate::routes::Access::Optional) policy, regardless of
+    /// the [optional](JwtLayer::with_optional) mode of the layer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     axum::{Router, routing},
+    ///     axum_jwt::{Decoder, jsonwebtoken::DecodingKey, routes::Routes},
+    /// };
+    ///
+    /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    ///
+    /// # async fn handler() {}
+    /// let app = Router::new()
+    ///     .route("/", routing::get(handler))
+    ///     .route("/login", routing::post(handler))
+    ///     .layer(axum_jwt::layer(decoder).with_routes(Routes::new().public("/login")));
+    /// # let _: Router = app;
+    /// ```
+    #[cfg(feature = "routes")]
+    pub fn with_routes(mut self, routes: Routes) -> Self {
+        self.routes = Some(Arc::new(routes));
         self
     }
-}
 
-impl<I, H> JwtLayer<I, H> {
     /// Applies a token extractor to the layer.
     ///
     /// By default, the token is extracted from the `Authorization` header using
     /// the `Bearer` scheme. If you want to change this behavior, create a new type
-    /// and implement [`Extract`] for it. Then, you can pass this type into the
-    /// layer configuration:
+    /// and implement [`Extract`] for it. Then, you can pass a value of this type
+    /// into the layer configuration. The layer keeps the value, so it can carry
+    /// runtime configuration, for example, a header name loaded from a config
+    /// file. The [`Token`] and other extractors with the same extractor type
+    /// use this value as well:
     ///
     /// ```
     /// use {
-    ///     axum::{Extension, Router, http::request::Parts, routing},
-    ///     axum_jwt::{Decoder, Extract, Token, jsonwebtoken::DecodingKey},
+    ///     axum::{
+    ///         Extension, Router,
+    ///         http::{HeaderName, request::Parts},
+    ///         routing,
+    ///     },
+    ///     axum_jwt::{Decoder, Error, Extract, Token, jsonwebtoken::DecodingKey},
+    ///     std::borrow::Cow,
     /// };
     ///
-    /// struct Custom;
+    /// struct Custom {
+    ///     header: HeaderName,
+    /// }
     ///
     /// impl Extract for Custom {
-    ///     fn extract(parts: &mut Parts) -> Option<&str> {
-    ///         parts.headers.get("X-Auth-Token")?.to_str().ok()
+    ///     fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+    ///         let value = parts.headers.get(&self.header).ok_or(Error::Missing)?;
+    ///         value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
     ///     }
     /// }
     ///
@@ -342,64 +753,90 @@ impl<I, H> JwtLayer<I, H> {
     /// }
     ///
     /// let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    /// let custom = Custom {
+    ///     header: HeaderName::from_static("x-auth-token"),
+    /// };
     ///
     /// let app = Router::new()
     ///     .route("/", routing::get(hello))
-    ///     .layer(axum_jwt::layer(decoder).with_extract(Custom));
+    ///     .layer(axum_jwt::layer(decoder).with_extract(custom));
     /// # let _: Router = app;
     /// ```
-    pub fn with_extract<X>(self, extract: X) -> JwtLayer<I, H, X>
+    pub fn with_extract<Y>(self, extract: Y) -> JwtLayer<I, H, Y, St, V>
     where
-        X: Extract,
+        Y: Extract,
     {
-        _ = extract;
         JwtLayer {
-            decoder: self.decoder,
+            verifier: self.verifier,
             validate: self.validate,
             store: self.store,
-            extract: PhantomData,
+            extract: Arc::new(extract),
+            csrf: self.csrf,
+            #[cfg(feature = "routes")]
+            routes: self.routes,
+            optional: self.optional,
+            claims: PhantomData,
         }
     }
 }
 
-impl<I, H, X> Clone for JwtLayer<I, H, X>
+impl<I, H, X, St, V> Clone for JwtLayer<I, H, X, St, V>
 where
     H: Clone,
+    St: Clone,
+    V: Clone,
 {
     fn clone(&self) -> Self {
         Self {
-            decoder: self.decoder.clone(),
+            verifier: self.verifier.clone(),
             validate: self.validate.clone(),
-            store: self.store,
-            extract: PhantomData,
+            store: self.store.clone(),
+            extract: self.extract.clone(),
+            csrf: self.csrf.clone(),
+            #[cfg(feature = "routes")]
+            routes: self.routes.clone(),
+            optional: self.optional,
+            claims: PhantomData,
         }
     }
 }
 
-impl<I, H, X> fmt::Debug for JwtLayer<I, H, X> {
+impl<I, H, X, St, V> fmt::Debug for JwtLayer<I, H, X, St, V>
+where
+    V: fmt::Debug,
+{
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("JwtLayer")
-            .field("decoder", &self.decoder)
+            .field("verifier", &self.verifier)
             .field("validate", &"..")
             .field("store", &"..")
-            .field("extract", &any::type_name::<H>())
+            .field("extract", &any::type_name::<X>())
             .finish()
     }
 }
 
-impl<S, I, H, X> Layer<S> for JwtLayer<I, H, X>
+impl<S, I, H, X, St, V> Layer<S> for JwtLayer<I, H, X, St, V>
 where
     H: Clone,
+    St: Clone,
+    V: Clone,
 {
-    type Service = Jwt<S, I, H, X>;
+    type Service = Jwt<S, I, H, X, St, V>;
 
     fn layer(&self, svc: S) -> Self::Service {
         Jwt {
             svc,
-            decoder: self.decoder.clone(),
-            validate: self.validate.clone(),
-            store: self.store,
-            extract: PhantomData,
+            auth: Auth {
+                verifier: self.verifier.clone(),
+                validate: self.validate.clone(),
+                store: self.store.clone(),
+                extract: self.extract.clone(),
+                csrf: self.csrf.clone(),
+                #[cfg(feature = "routes")]
+                routes: self.routes.clone(),
+                optional: self.optional,
+                claims: PhantomData,
+            },
         }
     }
 }
@@ -411,25 +848,27 @@ pub trait Validate<I> {
 }
 
 /// The output value of the [validation](Validate).
+///
+/// The error is boxed, since it's only produced when the token is rejected.
 pub trait Output {
-    fn output(self) -> Option<Response>;
+    fn output(self) -> Result<(), Box<Error<Response>>>;
 }
 
 impl<E> Output for Result<(), E>
 where
     E: IntoResponse,
 {
-    fn output(self) -> Option<Response> {
-        self.err().map(E::into_response)
+    fn output(self) -> Result<(), Box<Error<Response>>> {
+        self.map_err(|e| Box::new(Error::Custom(e.into_response())))
     }
 }
 
 impl Output for bool {
-    fn output(self) -> Option<Response> {
+    fn output(self) -> Result<(), Box<Error<Response>>> {
         if self {
-            None
+            Ok(())
         } else {
-            Some(StatusCode::UNAUTHORIZED.into_response())
+            Err(Box::new(Error::Rejected))
         }
     }
 }
@@ -459,44 +898,317 @@ where
     }
 }
 
+/// Filter with access to the request.
+///
+/// To create it, call the [`with_request_filter`](JwtLayer::with_request_filter)
+/// method.
+#[derive(Clone)]
+pub struct RequestFilter<H>(H);
+
+/// Asynchronous filter.
+///
+/// To create it, call the [`with_async_filter`](JwtLayer::with_async_filter)
+/// method.
+#[derive(Clone)]
+pub struct AsyncFilter<H>(H);
+
+/// Combination of two filters, the token must pass both of them.
+///
+/// It's created when a filter is set on a layer that already has one.
+/// The filters are applied in the order they were set. If the first one
+/// rejects the token, the second one isn't applied.
+#[derive(Clone)]
+pub struct And<A, B>(A, B);
+
+/// Stores the token in the extensions.
+///
+/// To create it, call the [`store_to_extension`](JwtLayer::store_to_extension)
+/// method.
+#[derive(Clone)]
+pub struct StoreToken;
+
+/// Stores a value derived from the token in the extensions.
+///
+/// To create it, call the [`store_with`](JwtLayer::store_with) method.
+#[derive(Clone)]
+pub struct StoreWith<F, St> {
+    map: F,
+    next: St,
+}
+
+mod store {
+    use super::*;
+
+    /// Stores the validated token data in the request extensions.
+    pub trait Store<I> {
+        fn store(&self, token: Token<I>, extensions: &mut Extensions);
+    }
+
+    impl<I> Store<I> for Discard {
+        fn store(&self, _: Token<I>, _: &mut Extensions) {}
+    }
+
+    impl<I> Store<I> for StoreToken
+    where
+        I: Clone + Send + Sync + 'static,
+    {
+        fn store(&self, token: Token<I>, extensions: &mut Extensions) {
+            extensions.insert(token);
+        }
+    }
+
+    impl<I, F, V, St> Store<I> for StoreWith<F, St>
+    where
+        F: Fn(&Token<I>) -> V,
+        V: Clone + Send + Sync + 'static,
+        St: Store<I>,
+    {
+        fn store(&self, token: Token<I>, extensions: &mut Extensions) {
+            // The token is moved into the next store, so the value is
+            // computed first but inserted last to override earlier values
+            let value = (self.map)(&token);
+            self.next.store(token, extensions);
+            extensions.insert(value);
+        }
+    }
+
+    /// Adds storing of the token itself.
+    ///
+    /// The token is moved into the extensions, so it's stored first,
+    /// before all derived values.
+    pub trait WithToken {
+        type Output;
+        fn with_token(self) -> Self::Output;
+    }
+
+    impl WithToken for Discard {
+        type Output = StoreToken;
+
+        fn with_token(self) -> Self::Output {
+            StoreToken
+        }
+    }
+
+    impl WithToken for StoreToken {
+        type Output = Self;
+
+        fn with_token(self) -> Self::Output {
+            self
+        }
+    }
+
+    impl<F, St> WithToken for StoreWith<F, St>
+    where
+        St: WithToken,
+    {
+        type Output = StoreWith<F, St::Output>;
+
+        fn with_token(self) -> Self::Output {
+            StoreWith {
+                map: self.map,
+                next: self.next.with_token(),
+            }
+        }
+    }
+}
+
+mod filter {
+    use super::*;
+
+    pub enum Filtered {
+        Passed,
+        Rejected(Error<Response>),
+        Pending(Pending),
+    }
+
+    impl Filtered {
+        fn new<O>(output: O) -> Self
+        where
+            O: Output,
+        {
+            match output.output() {
+                Ok(()) => Self::Passed,
+                Err(e) => Self::Rejected(*e),
+            }
+        }
+    }
+
+    /// The future of a pending filter.
+    pub type Pending = Pin<Box<dyn Future<Output = Result<(), Error<Response>>> + Send>>;
+
+    /// The filter applied by the middleware.
+    ///
+    /// This trait unifies [synchronous](Validate) and
+    /// [asynchronous](AsyncFilter) filters. Besides the decoded token,
+    /// the filter receives its claims as a JSON value.
+    pub trait Filter<I> {
+        fn filter(&mut self, token: &Token<I>, claims: &Value, parts: &Parts) -> Filtered;
+    }
+
+    impl<I> Filter<I> for Discard {
+        fn filter(&mut self, _: &Token<I>, _: &Value, _: &Parts) -> Filtered {
+            Filtered::Passed
+        }
+    }
+
+    impl<F, I, O> Filter<I> for F
+    where
+        F: FnMut(&Token<I>) -> O,
+        I: DeserializeOwned,
+        O: Output,
+    {
+        fn filter(&mut self, token: &Token<I>, _: &Value, _: &Parts) -> Filtered {
+            Filtered::new(self.validate(token))
+        }
+    }
+
+    impl<H, I, O> Filter<I> for RequestFilter<H>
+    where
+        H: FnMut(&Token<I>, &Parts) -> O,
+        O: Output,
+    {
+        fn filter(&mut self, token: &Token<I>, _: &Value, parts: &Parts) -> Filtered {
+            Filtered::new((self.0)(token, parts))
+        }
+    }
+
+    impl<H, I, F> Filter<I> for AsyncFilter<H>
+    where
+        H: FnMut(&Token<I>) -> F,
+        F: Future<Output: Output> + Send + 'static,
+    {
+        fn filter(&mut self, token: &Token<I>, _: &Value, _: &Parts) -> Filtered {
+            let fut = (self.0)(token);
+            Filtered::Pending(Box::pin(async move { fut.await.output().map_err(|e| *e) }))
+        }
+    }
+
+    impl<A, B, I> Filter<I> for And<A, B>
+    where
+        A: Filter<I>,
+        B: Filter<I>,
+    {
+        fn filter(&mut self, token: &Token<I>, claims: &Value, parts: &Parts) -> Filtered {
+            let first = match self.0.filter(token, claims, parts) {
+                Filtered::Passed => None,
+                Filtered::Rejected(e) => return Filtered::Rejected(e),
+                Filtered::Pending(fut) => Some(fut),
+            };
+
+            match (first, self.1.filter(token, claims, parts)) {
+                (_, Filtered::Rejected(e)) => Filtered::Rejected(e),
+                (None, filtered) => filtered,
+                (Some(a), Filtered::Passed) => Filtered::Pending(a),
+                (Some(a), Filtered::Pending(b)) => Filtered::Pending(Box::pin(async move {
+                    a.await?;
+                    b.await
+                })),
+            }
+        }
+    }
+
+    impl<I> Filter<I> for Require {
+        fn filter(&mut self, _: &Token<I>, claims: &Value, _: &Parts) -> Filtered {
+            match self.check(claims) {
+                Ok(()) => Filtered::Passed,
+                Err(e) => Filtered::Rejected(e.widen()),
+            }
+        }
+    }
+
+    /// Combines the filter with the next one.
+    pub trait Compose<I, G> {
+        type Output;
+        fn compose(self, next: G) -> Self::Output;
+    }
+
+    impl<I, G> Compose<I, G> for Discard {
+        type Output = G;
+
+        fn compose(self, next: G) -> Self::Output {
+            next
+        }
+    }
+
+    impl<F, I, O, G> Compose<I, G> for F
+    where
+        F: FnMut(&Token<I>) -> O,
+    {
+        type Output = And<Self, G>;
+
+        fn compose(self, next: G) -> Self::Output {
+            And(self, next)
+        }
+    }
+
+    impl<H, I, G> Compose<I, G> for RequestFilter<H> {
+        type Output = And<Self, G>;
+
+        fn compose(self, next: G) -> Self::Output {
+            And(self, next)
+        }
+    }
+
+    impl<H, I, G> Compose<I, G> for AsyncFilter<H> {
+        type Output = And<Self, G>;
+
+        fn compose(self, next: G) -> Self::Output {
+            And(self, next)
+        }
+    }
+
+    impl<I, G> Compose<I, G> for Require {
+        type Output = And<Self, G>;
+
+        fn compose(self, next: G) -> Self::Output {
+            And(self, next)
+        }
+    }
+
+    impl<A, B, I, G> Compose<I, G> for And<A, B> {
+        type Output = And<Self, G>;
+
+        fn compose(self, next: G) -> Self::Output {
+            And(self, next)
+        }
+    }
+}
+
 /// Axum [middleware] for token validation.
 ///
 /// [middleware]: https://docs.rs/axum/latest/axum/middleware/index.html
 ///
 /// To configure the layer and create the middleware service, call
 /// the [`layer`] function.
-pub struct Jwt<S, I, H = Discard, X = Bearer> {
+pub struct Jwt<S, I, H = Discard, X = Bearer, St = Discard, V = Decoder> {
     svc: S,
-    decoder: Decoder,
-    validate: H,
-    store: fn(Token<I>, &mut Extensions),
-    extract: PhantomData<fn() -> X>,
+    auth: Auth<I, H, X, St, V>,
 }
 
-impl<S, I, H, X> Clone for Jwt<S, I, H, X>
+impl<S, I, H, X, St, V> Clone for Jwt<S, I, H, X, St, V>
 where
     S: Clone,
     H: Clone,
+    St: Clone,
+    V: Clone,
 {
     fn clone(&self) -> Self {
         Self {
             svc: self.svc.clone(),
-            decoder: self.decoder.clone(),
-            validate: self.validate.clone(),
-            store: self.store,
-            extract: PhantomData,
+            auth: self.auth.clone(),
         }
     }
 }
 
-impl<S, I, H, X> fmt::Debug for Jwt<S, I, H, X>
+impl<S, I, H, X, St, V> fmt::Debug for Jwt<S, I, H, X, St, V>
 where
     S: fmt::Debug,
+    V: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Jwt")
             .field("svc", &self.svc)
-            .field("decoder", &self.decoder)
+            .field("verifier", &self.auth.verifier)
             .field("validate", &"..")
             .field("store", &"..")
             .field("extract", &any::type_name::<X>())
@@ -504,12 +1216,26 @@ where
     }
 }
 
-impl<S, I, H, X> Service<Request> for Jwt<S, I, H, X>
+impl<S, I, H, X, St, V> Jwt<S, I, H, X, St, V>
 where
-    S: Service<Request> + Clone,
-    I: DeserializeOwned,
-    H: Validate<I>,
-    X: Extract,
+    S: Clone,
+{
+    /// Takes the inner service, which is ready to be called, leaving its
+    /// clone in its place.
+    fn take_svc(&mut self) -> S {
+        let clone = self.svc.clone();
+        mem::replace(&mut self.svc, clone)
+    }
+}
+
+impl<S, I, H, X, St, V> Service<Request> for Jwt<S, I, H, X, St, V>
+where
+    S: Service<Request> + Clone + Send + 'static,
+    I: DeserializeOwned + Send + 'static,
+    H: Filter<I> + Clone + Send + 'static,
+    X: Extract + Send + Sync + 'static,
+    St: Store<I> + Clone + Send + 'static,
+    V: Verifier,
     Result<S::Response, S::Error>: IntoResponse,
 {
     type Response = Response;
@@ -520,34 +1246,198 @@ where
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: Request) -> Self::Future {
-        let validate = |parts| -> Result<Token<I>, Error> {
-            let token = X::extract(parts).ok_or(Error::Extract)?;
-            let TokenData { header, claims }: TokenData<I> =
-                self.decoder.decode(token).map_err(Error::Jwt)?;
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        req.extensions_mut()
+            .insert(Configured(self.auth.extract.clone()));
 
-            Ok(Token::new(header, claims))
-        };
+        #[cfg(feature = "routes")]
+        let access = self.auth.routes.as_ref().map(|routes| {
+            let route = req.extensions().get::<MatchedPath>();
+            routes.access(route.map(MatchedPath::as_str)).clone()
+        });
+
+        #[cfg(not(feature = "routes"))]
+        let access = None;
 
+        #[cfg(feature = "routes")]
+        if let Some(Access::Public) = access {
+            return JwtFuture::not_ready(self.take_svc(), req);
+        }
+
+        // The token is verified synchronously if the verifier allows it,
+        // the future is allocated only if the verifier or the filter
+        // is pending
         let (mut parts, body) = req.into_parts();
-        match validate(&mut parts) {
-            Ok(token) => {
-                if let Some(res) = self.validate.validate(&token).output() {
-                    return JwtFuture::ready(res);
+        let verified = match self.auth.extract.extract(&mut parts) {
+            Ok(token) => match self.auth.verifier.verify_now(&token) {
+                Some(res) => {
+                    res.map(|data| Verified::new(&token, data, self.auth.verifier.clone()))
+                }
+                None => {
+                    let token = token.into_owned();
+                    let auth = self.auth.clone();
+                    let svc = self.take_svc();
+                    let auth = auth.verify(token, access, parts, body, svc);
+                    return JwtFuture::authenticating(Box::pin(auth));
                 }
+            },
+            Err(e) => Err(e),
+        };
 
-                (self.store)(token, &mut parts.extensions);
+        let svc = self.take_svc();
+        match self
+            .auth
+            .authenticate(verified, access.as_ref(), &mut parts)
+        {
+            Filtered::Passed => JwtFuture::not_ready(svc, Request::from_parts(parts, body)),
+            Filtered::Rejected(e) => JwtFuture::rejected(verify::reject(&self.auth.verifier, e)),
+            Filtered::Pending(fut) => {
+                let verifier = self.auth.verifier.clone();
+                JwtFuture::authenticating(Box::pin(async move {
+                    fut.await.map_err(|e| verify::reject(&verifier, e))?;
+                    Ok((svc, Request::from_parts(parts, body)))
+                }))
+            }
+        }
+    }
+}
+
+/// The configuration of the [middleware](Jwt).
+struct Auth<I, H, X, St, V> {
+    verifier: V,
+    validate: H,
+    store: St,
+    extract: Arc<X>,
+    csrf: Option<Arc<Csrf>>,
+    #[cfg(feature = "routes")]
+    routes: Option<Arc<Routes>>,
+    optional: Option<Optional>,
+    claims: PhantomData<fn() -> I>,
+}
+
+impl<I, H, X, St, V> Clone for Auth<I, H, X, St, V>
+where
+    H: Clone,
+    St: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            verifier: self.verifier.clone(),
+            validate: self.validate.clone(),
+            store: self.store.clone(),
+            extract: self.extract.clone(),
+            csrf: self.csrf.clone(),
+            #[cfg(feature = "routes")]
+            routes: self.routes.clone(),
+            optional: self.optional,
+            claims: PhantomData,
+        }
+    }
+}
 
-                let req = Request::from_parts(parts, body);
-                let clone = self.svc.clone();
-                let svc = mem::replace(&mut self.svc, clone);
-                JwtFuture::not_ready(svc, req)
+impl<I, H, X, St, V> Auth<I, H, X, St, V>
+where
+    I: DeserializeOwned,
+    H: Filter<I>,
+    X: Extract,
+    St: Store<I>,
+    V: Verifier,
+{
+    /// Verifies the token with the pending verifier, then authenticates
+    /// the request.
+    ///
+    /// Returns the request to pass to the inner service or the response
+    /// rejecting it.
+    async fn verify<S>(
+        mut self,
+        token: String,
+        access: Option<Access>,
+        mut parts: Parts,
+        body: Body,
+        svc: S,
+    ) -> Result<(S, Request), Response> {
+        let verified = match self.verifier.verify(&token).await {
+            Ok(data) => Ok(Verified::new(&token, data, self.verifier.clone())),
+            Err(e) => Err(e),
+        };
+
+        match self.authenticate(verified, access.as_ref(), &mut parts) {
+            Filtered::Passed => {}
+            Filtered::Rejected(e) => return Err(verify::reject(&self.verifier, e)),
+            Filtered::Pending(fut) => fut.await.map_err(|e| verify::reject(&self.verifier, e))?,
+        }
+
+        Ok((svc, Request::from_parts(parts, body)))
+    }
+
+    /// Authenticates the request with the verified token.
+    ///
+    /// If the request passes the checks, the token is stored in
+    /// the extensions. A pending filter is returned to be awaited, if it
+    /// rejects the token, the request isn't passed to the inner service.
+    fn authenticate(
+        &mut self,
+        verified: Result<Verified<V>, Error>,
+        access: Option<&Access>,
+        parts: &mut Parts,
+    ) -> Filtered {
+        let optional = match access {
+            None => self.optional,
+            #[cfg(feature = "routes")]
+            Some(Access::Optional) => Some(self.optional.unwrap_or_default()),
+            Some(_) => None,
+        };
+
+        let (token, verified) = match verified.and_then(|verified| self.accept(verified, parts)) {
+            Ok(accepted) => accepted,
+            Err(e) if optional.is_some_and(|optional| optional.is_anonymous(&e)) => {
+                // The ignored token is removed like the accepted one
+                self.extract.strip(parts);
+                return Filtered::Passed;
             }
-            Err(e) => JwtFuture::ready(e.into_response()),
+            Err(e) => return Filtered::Rejected(e.widen()),
+        };
+
+        #[cfg(feature = "routes")]
+        if let Some(Access::Require(require)) = access
+            && let Err(e) = require.check(&verified.data().claims)
+        {
+            return Filtered::Rejected(e.widen());
         }
+
+        let filtered = self.validate.filter(&token, &verified.data().claims, parts);
+
+        if let Filtered::Rejected(_) = filtered {
+            return filtered;
+        }
+
+        self.store.store(token, &mut parts.extensions);
+        parts.extensions.insert(verified);
+        self.extract.strip(parts);
+        filtered
+    }
+
+    /// Checks the CSRF protection and deserializes the claims of
+    /// the verified token.
+    fn accept(
+        &self,
+        verified: Verified<V>,
+        parts: &Parts,
+    ) -> Result<(Token<I>, Verified<V>), Error> {
+        if let Some(csrf) = self.csrf.as_deref().filter(|_| !parts.method.is_safe()) {
+            let expected = csrf.expected(&verified.data().claims)?;
+            csrf.verify(expected, &parts.headers)?;
+        }
+
+        let TokenData { header, claims } = verify::claims(verified.data())?;
+        Ok((Token::new(header, claims), verified))
     }
 }
 
+/// The future of the pending [authentication](Jwt).
+type Authenticate<S> = Pin<Box<dyn Future<Output = Result<(S, Request), Response>> + Send>>;
+
 pin_project_lite::pin_project! {
     /// Middleware future.
     pub struct JwtFuture<S>
@@ -563,15 +1453,21 @@ impl<S> JwtFuture<S>
 where
     S: Service<Request>,
 {
+    fn authenticating(auth: Authenticate<S>) -> Self {
+        Self {
+            state: State::Authenticating { auth },
+        }
+    }
+
     fn not_ready(svc: S, req: Request) -> Self {
         Self {
             state: State::NotReady { svc, req },
         }
     }
 
-    fn ready(res: Response) -> Self {
+    fn rejected(res: Response) -> Self {
         Self {
-            state: State::Ready { res },
+            state: State::Rejected { res },
         }
     }
 }
@@ -587,6 +1483,18 @@ where
         let mut state = self.project().state;
         let res = loop {
             match state.as_mut().project() {
+                StateProj::Authenticating { auth } => match task::ready!(auth.as_mut().poll(cx)) {
+                    Ok((svc, req)) => state.set(State::NotReady { svc, req }),
+                    Err(res) => {
+                        state.set(State::Done);
+                        break res;
+                    }
+                },
+                StateProj::Rejected { res } => {
+                    let res = mem::take(res);
+                    state.set(State::Done);
+                    break res;
+                }
                 StateProj::NotReady { svc, req } => {
                     if let Err(e) = task::ready!(svc.poll_ready(cx)) {
                         state.set(State::Done);
@@ -602,11 +1510,6 @@ where
                     state.set(State::Done);
                     break res.into_response();
                 }
-                StateProj::Ready { res } => {
-                    let res = mem::take(res);
-                    state.set(State::Done);
-                    break res;
-                }
                 StateProj::Done => panic!("polled after completion"),
             }
         };
@@ -618,12 +1521,13 @@ where
 pin_project_lite::pin_project! {
     #[project = StateProj]
     enum State<S, F> {
+        Authenticating { auth: Authenticate<S> },
+        Rejected { res: Response },
         NotReady { svc: S, req: Request },
         Called {
             #[pin]
             fut: F,
         },
-        Ready { res: Response },
         Done,
     }
 }
@@ -1,16 +1,35 @@
 #![cfg_attr(all(doc, not(doctest)), doc = include_str!("../README.md"))]
 
+pub mod authz;
+mod challenge;
+mod csrf;
 mod decode;
+mod encode;
 mod error;
 mod extract;
+pub mod introspect;
 pub mod layer;
+pub mod refresh;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod revoke;
+#[cfg(feature = "routes")]
+pub mod routes;
+mod verify;
 
 pub use {
     crate::{
-        decode::Decoder,
-        error::Error,
-        extract::{Bearer, Claims, Extract, Token},
+        challenge::{Challenge, Render},
+        csrf::Csrf,
+        decode::{Decoder, JwksError},
+        encode::{EncodeError, Encoder},
+        error::{Error, Rejection},
+        extract::{
+            Bearer, Claims, ClaimsWith, Cookie, Extract, First, FromState, Optional, Policy,
+            Protocol, Query, Raw, Sources, Subprotocol, Token,
+        },
         layer::layer,
+        verify::Verifier,
     },
     jsonwebtoken,
 };
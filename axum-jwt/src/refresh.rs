@@ -0,0 +1,540 @@
+//! Access and refresh token rotation.
+//!
+//! The [`Refresh`] type issues pairs of short-lived access tokens and
+//! long-lived refresh tokens. When the access token expires, the client
+//! exchanges the refresh token for a new pair. Each refresh token can be used
+//! only once: if a used token is presented again, it's likely been stolen, so
+//! the whole family of tokens descending from the same login is revoked.
+//!
+//! Refresh tokens are tracked by a user-supplied [storage](Store). Each refresh
+//! token carries a `jti` identifier and a `fam` claim naming its family.
+//! The custom claims of a refresh token are carried over to the new pair.
+//!
+//! Refresh tokens are validated with their own [decoder](Decoder), so they
+//! should be issued with a distinct audience and `typ` header, so that an
+//! access token can never be used as a refresh token and vice versa.
+//! If the decoder has a [revocation](crate::revoke) check, revoked refresh
+//! tokens are rejected as well.
+//!
+//! # Examples
+//!
+//! [`Refresh`] implements the [`Service`] trait and can be used as a token
+//! endpoint. It accepts the [OAuth 2.0 refresh request], that is,
+//! a form-encoded body with the `grant_type=refresh_token` and
+//! `refresh_token` parameters, and responds with a new pair in the [OAuth 2.0]
+//! format. For clients that don't speak OAuth, a JSON body with
+//! the `refresh_token` field is accepted as well. Client authentication
+//! isn't performed, since refresh tokens are bearer tokens on their own.
+//!
+//! [OAuth 2.0 refresh request]: https://www.rfc-editor.org/rfc/rfc6749#section-6
+//! [OAuth 2.0]: https://www.rfc-editor.org/rfc/rfc6749#section-5.1
+//!
+//! ```
+//! use {
+//!     axum::{Router, extract::State, http::StatusCode, routing},
+//!     axum_jwt::{
+//!         Decoder, Encoder,
+//!         jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation},
+//!         refresh::{Pair, Refresh, Store},
+//!     },
+//!     serde::Serialize,
+//!     std::{
+//!         collections::{HashMap, HashSet},
+//!         convert::Infallible,
+//!         sync::Mutex,
+//!     },
+//! };
+//!
+//! // Don't use this in production,
+//! // tokens are never evicted from the memory.
+//! #[derive(Default)]
+//! struct Memory {
+//!     families: Mutex<HashMap<String, HashSet<String>>>,
+//! }
+//!
+//! impl Store for Memory {
+//!     type Error = Infallible;
+//!
+//!     async fn issue(&self, family: &str, jti: &str, _: u64) -> Result<(), Self::Error> {
+//!         let mut families = self.families.lock().expect("lock families");
+//!         families.entry(family.to_owned()).or_default().insert(jti.to_owned());
+//!         Ok(())
+//!     }
+//!
+//!     async fn consume(&self, family: &str, jti: &str) -> Result<bool, Self::Error> {
+//!         let mut families = self.families.lock().expect("lock families");
+//!         Ok(families.get_mut(family).is_some_and(|jtis| jtis.remove(jti)))
+//!     }
+//!
+//!     async fn revoke(&self, family: &str) -> Result<(), Self::Error> {
+//!         self.families.lock().expect("lock families").remove(family);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     sub: String,
+//! }
+//!
+//! async fn login(State(refresh): State<Refresh<Memory>>) -> Result<Pair, StatusCode> {
+//!     // Check the user's credentials here
+//!     let user = User {
+//!         sub: "alice".to_owned(),
+//!     };
+//!
+//!     refresh
+//!         .issue(&user)
+//!         .await
+//!         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+//! }
+//!
+//! let access = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+//!
+//! let header = Header {
+//!     typ: Some("refresh+jwt".to_owned()),
+//!     ..Header::default()
+//! };
+//!
+//! let refresh = Encoder::new(EncodingKey::from_secret(b"refresh secret"), header)
+//!     .with_audience(["refresh"]);
+//!
+//! let mut validation = Validation::default();
+//! validation.set_audience(&["refresh"]);
+//! let decoder = Decoder::new(DecodingKey::from_secret(b"refresh secret"), validation);
+//!
+//! let refresh = Refresh::new(access, refresh, decoder, Memory::default());
+//!
+//! let app = Router::new()
+//!     .route("/login", routing::post(login))
+//!     .route_service("/token", refresh.clone())
+//!     .with_state(refresh);
+//! # let _: Router = app;
+//! ```
+
+use {
+    crate::{
+        decode::Decoder,
+        encode::{self, EncodeError, Encoder},
+        error::Error,
+        extract,
+        verify::Verifier,
+    },
+    axum_core::{
+        extract::{FromRequest, Request},
+        response::{IntoResponse, Response},
+    },
+    http::{StatusCode, header},
+    jsonwebtoken::{TokenData, errors::ErrorKind},
+    serde::{Deserialize, Serialize},
+    serde_json::{Map, Value},
+    std::{
+        convert::Infallible,
+        error, fmt,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    },
+    tower_service::Service,
+};
+
+/// Storage of issued refresh tokens.
+///
+/// The implementation must be safe to call concurrently, in particular,
+/// [`consume`](Store::consume) must succeed for a token only once.
+///
+/// During the rotation, the new refresh token is [issued](Store::issue)
+/// before the presented one is consumed. This way, if the storage fails
+/// in between, the presented token stays valid and the client can retry
+/// instead of being treated as reusing the token. The record of a new
+/// token that was never handed to the client is then left in the storage
+/// until it expires.
+pub trait Store: Send + Sync + 'static {
+    type Error: Send;
+
+    /// Records a newly issued refresh token of the family.
+    ///
+    /// The `exp` is the expiration time of the token as a UNIX timestamp,
+    /// after which the record is no longer needed.
+    fn issue(
+        &self,
+        family: &str,
+        jti: &str,
+        exp: u64,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Atomically marks the refresh token as used.
+    ///
+    /// Returns `true` if the token was issued and hasn't been used yet.
+    /// Returns `false` if the token was already used or its family
+    /// was revoked.
+    fn consume(
+        &self,
+        family: &str,
+        jti: &str,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+
+    /// Revokes all refresh tokens of the family.
+    ///
+    /// This is called when a reuse of a refresh token is detected.
+    fn revoke(&self, family: &str) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// A pair of access and refresh tokens.
+///
+/// The pair is serialized in the [OAuth 2.0] access token response format.
+///
+/// [OAuth 2.0]: https://www.rfc-editor.org/rfc/rfc6749#section-5.1
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Pair {
+    pub access_token: String,
+    pub token_type: String,
+
+    /// The lifetime of the access token in seconds.
+    pub expires_in: u64,
+    pub refresh_token: String,
+}
+
+impl IntoResponse for Pair {
+    fn into_response(self) -> Response {
+        match serde_json::to_vec(&self) {
+            Ok(body) => (
+                [
+                    (header::CONTENT_TYPE, "application/json"),
+                    (header::CACHE_CONTROL, "no-store"),
+                ],
+                body,
+            )
+                .into_response(),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+/// Errors that can occur during the token rotation.
+#[derive(Debug)]
+pub enum RefreshError<E> {
+    /// The refresh token is invalid.
+    Token(Error),
+
+    /// The refresh token was already used. The whole family is revoked.
+    Reused,
+
+    /// Failed to encode a token.
+    Encode(EncodeError),
+
+    /// Storage error.
+    Store(E),
+}
+
+impl<E> fmt::Display for RefreshError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Token(e) => write!(f, "invalid refresh token: {e}"),
+            Self::Reused => write!(f, "the refresh token was already used"),
+            Self::Encode(e) => write!(f, "failed to encode a token: {e}"),
+            Self::Store(e) => write!(f, "storage error: {e}"),
+        }
+    }
+}
+
+impl<E> error::Error for RefreshError<E>
+where
+    E: fmt::Debug + fmt::Display,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Token(e) => Some(e),
+            Self::Encode(e) => Some(e),
+            Self::Reused | Self::Store(_) => None,
+        }
+    }
+}
+
+impl<E> IntoResponse for RefreshError<E> {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Token(_) | Self::Reused => oauth_error("invalid_grant"),
+            Self::Encode(_) | Self::Store(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+/// Issuer and rotator of token pairs.
+///
+/// See the [module](self) documentation for more details.
+pub struct Refresh<S> {
+    inner: Arc<Inner<S>>,
+}
+
+impl<S> Refresh<S>
+where
+    S: Store,
+{
+    /// Creates a new token rotator.
+    ///
+    /// The `access` encoder issues access tokens, the `refresh` encoder
+    /// issues refresh tokens and the `decoder` validates refresh tokens.
+    /// Refresh tokens are additionally checked to have the same `typ` header
+    /// as the one configured in the `refresh` encoder.
+    pub fn new(access: Encoder, refresh: Encoder, decoder: Decoder, store: S) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                access,
+                refresh,
+                decoder,
+                store,
+            }),
+        }
+    }
+
+    /// Issues a new pair of tokens, starting a new family.
+    ///
+    /// Call this method when the user logs in.
+    pub async fn issue<T>(&self, claims: &T) -> Result<Pair, RefreshError<S::Error>>
+    where
+        T: Serialize,
+    {
+        let claims =
+            encode::to_object(claims).map_err(|e| RefreshError::Encode(EncodeError::Jwt(e)))?;
+        let family = encode::jti().map_err(RefreshError::Encode)?;
+        let (pair, next) = self.encode_pair(claims, family)?;
+        self.store(&next).await?;
+        Ok(pair)
+    }
+
+    /// Validates the refresh token and issues a new pair of tokens.
+    ///
+    /// The token is verified with the decoder, including its revocation
+    /// check. The refresh token is consumed. If it was already used,
+    /// the whole family is revoked and [`RefreshError::Reused`] is returned.
+    /// If the rotation fails for another reason, the token isn't consumed,
+    /// so the request can be retried.
+    pub async fn refresh(&self, token: &str) -> Result<Pair, RefreshError<S::Error>> {
+        let inner = &*self.inner;
+        let TokenData { header, claims } = inner
+            .decoder
+            .verify(token)
+            .await
+            .map_err(RefreshError::Token)?;
+
+        let invalid = || RefreshError::Token(Error::Jwt(ErrorKind::InvalidToken.into()));
+        let Value::Object(mut claims) = claims else {
+            return Err(invalid());
+        };
+
+        if header.typ != inner.refresh.header().typ {
+            return Err(invalid());
+        }
+
+        let (Some(Value::String(family)), Some(Value::String(jti))) =
+            (claims.remove(FAMILY), claims.remove("jti"))
+        else {
+            return Err(invalid());
+        };
+
+        // Store the new token before consuming the presented one,
+        // so that a failure in between doesn't look like a reuse on retry
+        let (pair, next) = self.encode_pair(claims, family)?;
+        self.store(&next).await?;
+
+        let consumed = inner
+            .store
+            .consume(&next.family, &jti)
+            .await
+            .map_err(RefreshError::Store)?;
+
+        if !consumed {
+            inner
+                .store
+                .revoke(&next.family)
+                .await
+                .map_err(RefreshError::Store)?;
+
+            return Err(RefreshError::Reused);
+        }
+
+        Ok(pair)
+    }
+
+    fn encode_pair(
+        &self,
+        mut claims: Map<String, Value>,
+        family: String,
+    ) -> Result<(Pair, Issued), RefreshError<S::Error>> {
+        let inner = &*self.inner;
+
+        // These claims are stamped by encoders
+        for name in ["iat", "nbf", "exp", "jti", "iss", "aud"] {
+            claims.remove(name);
+        }
+
+        let access_token = inner.access.encode(&claims).map_err(RefreshError::Encode)?;
+
+        let jti = encode::jti().map_err(RefreshError::Encode)?;
+        let exp = jsonwebtoken::get_current_timestamp()
+            .saturating_add(inner.refresh.lifetime().as_secs());
+
+        claims.insert("exp".to_owned(), exp.into());
+        claims.insert("jti".to_owned(), jti.as_str().into());
+        claims.insert(FAMILY.to_owned(), family.as_str().into());
+        let refresh_token = inner
+            .refresh
+            .encode(&claims)
+            .map_err(RefreshError::Encode)?;
+
+        let pair = Pair {
+            access_token,
+            token_type: "Bearer".to_owned(),
+            expires_in: inner.access.lifetime().as_secs(),
+            refresh_token,
+        };
+
+        Ok((pair, Issued { family, jti, exp }))
+    }
+
+    async fn store(&self, issued: &Issued) -> Result<(), RefreshError<S::Error>> {
+        self.inner
+            .store
+            .issue(&issued.family, &issued.jti, issued.exp)
+            .await
+            .map_err(RefreshError::Store)
+    }
+
+    async fn handle(self, req: Request) -> Response {
+        let form = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+
+        let body = match String::from_request(req, &()).await {
+            Ok(body) => body,
+            Err(e) => return e.into_response(),
+        };
+
+        let grant = if form {
+            Grant::from_form(&body)
+        } else {
+            serde_json::from_str(&body).ok()
+        };
+
+        let Some(Grant {
+            grant_type,
+            refresh_token,
+        }) = grant
+        else {
+            return oauth_error("invalid_request");
+        };
+
+        if grant_type.is_some_and(|grant_type| grant_type != "refresh_token") {
+            return oauth_error("unsupported_grant_type");
+        }
+
+        match self.refresh(&refresh_token).await {
+            Ok(pair) => pair.into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+impl<S> Clone for Refresh<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for Refresh<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Refresh")
+            .field("access", &self.inner.access)
+            .field("refresh", &self.inner.refresh)
+            .field("decoder", &self.inner.decoder)
+            .field("store", &"..")
+            .finish()
+    }
+}
+
+impl<S> Service<Request> for Refresh<S>
+where
+    S: Store,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let refresh = self.clone();
+        Box::pin(async move { Ok(refresh.handle(req).await) })
+    }
+}
+
+/// A newly issued refresh token.
+struct Issued {
+    family: String,
+    jti: String,
+    exp: u64,
+}
+
+/// The refresh token request.
+///
+/// The `grant_type` is required in the form and optional in JSON.
+#[derive(Deserialize)]
+struct Grant {
+    grant_type: Option<String>,
+    refresh_token: String,
+}
+
+impl Grant {
+    /// Parses the form-encoded request.
+    fn from_form(body: &str) -> Option<Self> {
+        let mut grant_type = None;
+        let mut refresh_token = None;
+        for pair in body.split('&') {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = extract::decode(value)?.into_owned();
+            match name {
+                "grant_type" => grant_type = Some(value),
+                "refresh_token" => refresh_token = Some(value),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            grant_type: Some(grant_type?),
+            refresh_token: refresh_token?,
+        })
+    }
+}
+
+/// The claim naming the token family.
+const FAMILY: &str = "fam";
+
+struct Inner<S> {
+    access: Encoder,
+    refresh: Encoder,
+    decoder: Decoder,
+    store: S,
+}
+
+fn oauth_error(error: &'static str) -> Response {
+    let body = format!(r#"{{"error":"{error}"}}"#);
+    (
+        StatusCode::BAD_REQUEST,
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,710 @@
+//! Remote key source.
+//!
+//! Identity providers usually publish their signing keys as a [JWK set]
+//! and rotate them from time to time. The [`Remote`] builder creates
+//! a [decoder](Decoder) whose keys are fetched from such a source and
+//! refreshed in the background, so the router doesn't need to be rebuilt
+//! when the keys change.
+//!
+//! The set is refetched when its cached copy expires. The cache lifetime is
+//! taken from the `max-age` directive of the `Cache-Control` response header,
+//! or from the configured [TTL](Remote::with_ttl) if the header is absent.
+//! Additionally, when a token arrives with a `kid` the decoder hasn't seen,
+//! the set is refetched on demand, but no more often than the configured
+//! [cooldown](Remote::with_cooldown) allows. Such a token is rejected, but
+//! once the new keys are fetched, subsequent tokens are accepted.
+//!
+//! [JWK set]: JwkSet
+//!
+//! This module requires the `remote` feature and the [tokio] runtime.
+//!
+//! [tokio]: https://docs.rs/tokio
+//!
+//! # Examples
+//!
+//! The way of fetching the set is defined by the [`Fetch`] trait. This allows
+//! you to use any HTTP client or, for example, a local stub in tests.
+//! With the `reqwest` feature, the [`Http`] fetcher requests the set
+//! with the [reqwest] client.
+//!
+//! [reqwest]: https://docs.rs/reqwest
+//!
+//! ```
+//! use {
+//!     axum::{Router, http::Response, routing},
+//!     axum_jwt::{
+//!         jsonwebtoken::{Algorithm, Validation, jwk::JwkSet},
+//!         remote::{Fetch, Remote},
+//!     },
+//!     std::convert::Infallible,
+//! };
+//!
+//! struct Provider;
+//!
+//! impl Fetch for Provider {
+//!     type Error = Infallible;
+//!
+//!     async fn fetch(&self) -> Result<Response<JwkSet>, Self::Error> {
+//!         // Request the set from the provider here
+//!         Ok(Response::new(JwkSet { keys: vec![] }))
+//!     }
+//! }
+//!
+//! # async fn hello() {}
+//! # async fn f() -> Result<(), axum_jwt::remote::FetchError<Infallible>> {
+//! let decoder = Remote::new(Provider)
+//!     .with_validation(Validation::new(Algorithm::RS256))
+//!     .spawn()
+//!     .await?;
+//!
+//! let app = Router::new()
+//!     .route("/", routing::get(hello))
+//!     .with_state(decoder);
+//! # let _: Router = app;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::decode::{Decoder, JwksError, Keys, Shared},
+    http::{HeaderMap, Response, header},
+    jsonwebtoken::{Algorithm, Validation, jwk::JwkSet},
+    std::{
+        error, fmt,
+        sync::{Arc, Weak},
+        time::Duration,
+    },
+    tokio::{
+        sync::Notify,
+        time::{self, Instant},
+    },
+};
+
+/// Trait for fetching a [JWK set](JwkSet).
+///
+/// The response headers are used to determine how long the set can be cached.
+pub trait Fetch: Send + Sync + 'static {
+    type Error: Send;
+    fn fetch(&self) -> impl Future<Output = Result<Response<JwkSet>, Self::Error>> + Send;
+}
+
+/// Errors that can occur when the key set is fetched for the first time.
+#[derive(Debug)]
+pub enum FetchError<E> {
+    /// Failed to fetch the key set.
+    Fetch(E),
+
+    /// The key set is invalid.
+    Jwk(JwksError),
+}
+
+impl<E> fmt::Display for FetchError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch(e) => write!(f, "failed to fetch the key set: {e}"),
+            Self::Jwk(e) => write!(f, "invalid key set: {e}"),
+        }
+    }
+}
+
+impl<E> error::Error for FetchError<E>
+where
+    E: fmt::Debug + fmt::Display,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Fetch(_) => None,
+            Self::Jwk(e) => Some(e),
+        }
+    }
+}
+
+/// A builder of a [decoder](Decoder) with remote keys.
+///
+/// See the [module](self) documentation for more details.
+pub struct Remote<F>
+where
+    F: Fetch,
+{
+    fetch: F,
+    validation: Validation,
+    ttl: Duration,
+    cooldown: Duration,
+    report: Option<Report<F::Error>>,
+}
+
+type Report<E> = Box<dyn Fn(FetchError<E>) + Send + Sync>;
+
+impl<F> Remote<F>
+where
+    F: Fetch,
+{
+    /// Creates a new builder with the provided fetcher.
+    ///
+    /// By default, the validation expects tokens signed with
+    /// the [`RS256`](Algorithm::RS256) algorithm, the TTL is 5 minutes and
+    /// the cooldown is 30 seconds.
+    pub fn new(fetch: F) -> Self {
+        Self {
+            fetch,
+            validation: Validation::new(Algorithm::RS256),
+            ttl: Duration::from_secs(300),
+            cooldown: Duration::from_secs(30),
+            report: None,
+        }
+    }
+
+    /// Sets the validation.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Sets the cache lifetime of the key set used when the response
+    /// has no `Cache-Control` header.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the minimum interval between two fetches.
+    ///
+    /// This limits on-demand fetches triggered by tokens with unknown `kid`
+    /// and is also used as a retry interval if a fetch fails.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sets a callback to report errors of the background refresh.
+    ///
+    /// When a refresh fails, the decoder keeps the previous keys and
+    /// the fetch is retried after the [cooldown](Remote::with_cooldown).
+    /// The callback is called with each such error, for example, to log it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axum_jwt::remote::{Fetch, Remote};
+    ///
+    /// fn remote<F>(fetch: F) -> Remote<F>
+    /// where
+    ///     F: Fetch<Error: std::fmt::Display>,
+    /// {
+    ///     Remote::new(fetch).with_report(|e| eprintln!("{e}"))
+    /// }
+    /// ```
+    pub fn with_report<R>(mut self, report: R) -> Self
+    where
+        R: Fn(FetchError<F::Error>) + Send + Sync + 'static,
+    {
+        self.report = Some(Box::new(report));
+        self
+    }
+
+    /// Fetches the key set and creates the decoder.
+    ///
+    /// After the first successful fetch, the key set is refreshed
+    /// in a background task. The task stops once all clones of
+    /// the decoder are dropped.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if called outside of the tokio runtime.
+    pub async fn spawn(self) -> Result<Decoder, FetchError<F::Error>> {
+        let res = self.fetch.fetch().await.map_err(FetchError::Fetch)?;
+        let notify = Arc::new(Notify::new());
+        let decoder = Decoder::with_refresh(res.body(), self.validation.clone(), {
+            let notify = notify.clone();
+            move || notify.notify_one()
+        })
+        .map_err(FetchError::Jwk)?;
+
+        let ttl = self.ttl(res.headers());
+        tokio::spawn(self.run(decoder.downgrade(), notify, ttl));
+        Ok(decoder)
+    }
+
+    async fn run(self, shared: Weak<Shared>, notify: Arc<Notify>, mut ttl: Duration) {
+        let mut fetched = Instant::now();
+        loop {
+            if time::timeout(ttl, notify.notified()).await.is_ok() {
+                // Unknown `kid` was found, but don't fetch too often
+                time::sleep_until(fetched + self.cooldown).await;
+            }
+
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+
+            let res = self.fetch.fetch().await.map_err(FetchError::Fetch);
+            ttl = match res.and_then(|res| {
+                let keys = Keys::from_jwks(res.body()).map_err(FetchError::Jwk)?;
+                Ok((keys, res))
+            }) {
+                Ok((keys, res)) => {
+                    shared.replace_keys(keys);
+                    self.ttl(res.headers())
+                }
+                Err(e) => {
+                    if let Some(report) = &self.report {
+                        report(e);
+                    }
+
+                    self.cooldown
+                }
+            };
+
+            fetched = Instant::now();
+        }
+    }
+
+    fn ttl(&self, headers: &HeaderMap) -> Duration {
+        max_age(headers).unwrap_or(self.ttl).max(self.cooldown)
+    }
+}
+
+/// A [fetcher](Fetch) of the key set over HTTP.
+///
+/// The set is requested with the [reqwest] client. This requires
+/// the `reqwest` feature. The client is built without TLS support, enable
+/// one of the TLS features of reqwest, like `rustls-tls`, to fetch the set
+/// over HTTPS.
+///
+/// [reqwest]: https://docs.rs/reqwest
+///
+/// # Examples
+///
+/// ```
+/// use axum_jwt::{
+///     jsonwebtoken::{Algorithm, Validation},
+///     remote::{Http, Remote},
+/// };
+///
+/// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// let url = "https://example.com/.well-known/jwks.json".parse()?;
+/// let decoder = Remote::new(Http::new(url))
+///     .with_validation(Validation::new(Algorithm::RS256))
+///     .with_report(|e| eprintln!("{e}"))
+///     .spawn()
+///     .await?;
+/// # _ = decoder;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "reqwest")]
+pub struct Http {
+    client: reqwest::Client,
+    url: reqwest::Url,
+}
+
+#[cfg(feature = "reqwest")]
+impl Http {
+    /// Creates a new fetcher of the set from the provided URL.
+    pub fn new(url: reqwest::Url) -> Self {
+        Self::with_client(reqwest::Client::new(), url)
+    }
+
+    /// Creates a new fetcher with the provided client.
+    pub fn with_client(client: reqwest::Client, url: reqwest::Url) -> Self {
+        Self { client, url }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Fetch for Http {
+    type Error = HttpError;
+
+    async fn fetch(&self) -> Result<Response<JwkSet>, Self::Error> {
+        let res = self
+            .client
+            .get(self.url.clone())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(HttpError::Request)?;
+
+        let headers = res.headers().clone();
+        let body = res.bytes().await.map_err(HttpError::Request)?;
+        let jwks = serde_json::from_slice(&body).map_err(HttpError::Parse)?;
+
+        let mut res = Response::new(jwks);
+        *res.headers_mut() = headers;
+        Ok(res)
+    }
+}
+
+/// Errors of the [`Http`] fetcher.
+#[cfg(feature = "reqwest")]
+#[derive(Debug)]
+pub enum HttpError {
+    /// The request failed or the response has an error status.
+    Request(reqwest::Error),
+
+    /// The response body isn't a valid key set.
+    Parse(serde_json::Error),
+}
+
+#[cfg(feature = "reqwest")]
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "request error: {e}"),
+            Self::Parse(e) => write!(f, "invalid response: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl error::Error for HttpError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Request(e) => Some(e),
+            Self::Parse(e) => Some(e),
+        }
+    }
+}
+
+fn max_age(headers: &HeaderMap) -> Option<Duration> {
+    let directives = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    let mut max_age = None;
+    for directive in directives {
+        let directive = directive.trim();
+        if directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+        {
+            return Some(Duration::ZERO);
+        }
+
+        if let Some((name, secs)) = directive.split_once('=')
+            && name.trim().eq_ignore_ascii_case("max-age")
+            && max_age.is_none()
+        {
+            max_age = Some(secs.trim().trim_matches('"').parse().ok());
+        }
+    }
+
+    max_age.flatten().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::Error,
+        http::HeaderValue,
+        jsonwebtoken::{EncodingKey, Header},
+        serde_json::{Value, json},
+        std::sync::{
+            Mutex,
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+        },
+    };
+
+    struct Provider {
+        fetches: AtomicUsize,
+        kid: Mutex<&'static str>,
+        cache_control: Option<&'static str>,
+        unavailable: AtomicBool,
+    }
+
+    impl Provider {
+        fn new(cache_control: Option<&'static str>) -> Arc<Self> {
+            Arc::new(Self {
+                fetches: AtomicUsize::new(0),
+                kid: Mutex::new("a"),
+                cache_control,
+                unavailable: AtomicBool::new(false),
+            })
+        }
+
+        fn set_unavailable(&self, unavailable: bool) {
+            self.unavailable.store(unavailable, Ordering::Relaxed);
+        }
+
+        fn fetches(&self) -> usize {
+            self.fetches.load(Ordering::Relaxed)
+        }
+
+        fn rotate(&self, kid: &'static str) {
+            *self.kid.lock().expect("lock the kid") = kid;
+        }
+    }
+
+    struct Stub(Arc<Provider>);
+
+    impl Fetch for Stub {
+        type Error = &'static str;
+
+        async fn fetch(&self) -> Result<Response<JwkSet>, Self::Error> {
+            self.0.fetches.fetch_add(1, Ordering::Relaxed);
+            if self.0.unavailable.load(Ordering::Relaxed) {
+                return Err("unavailable");
+            }
+
+            let kid = *self.0.kid.lock().expect("lock the kid");
+            let keys = json!({ "keys": [{ "kty": "oct", "kid": kid, "k": "c2VjcmV0" }] });
+            let keys = serde_json::from_value(keys).expect("the key set");
+
+            let mut res = Response::new(keys);
+            if let Some(value) = self.0.cache_control {
+                let value = HeaderValue::from_static(value);
+                res.headers_mut().insert(header::CACHE_CONTROL, value);
+            }
+
+            Ok(res)
+        }
+    }
+
+    async fn spawn(provider: &Arc<Provider>, ttl: u64) -> Decoder {
+        Remote::new(Stub(provider.clone()))
+            .with_validation(Validation::default())
+            .with_ttl(Duration::from_secs(ttl))
+            .with_cooldown(Duration::from_secs(30))
+            .spawn()
+            .await
+            .expect("fetch the keys")
+    }
+
+    fn token(kid: Option<&str>) -> String {
+        let header = Header {
+            kid: kid.map(str::to_owned),
+            ..Header::default()
+        };
+
+        let exp = jsonwebtoken::get_current_timestamp() + 3600;
+        let claims = json!({ "sub": "alice", "exp": exp });
+        let key = EncodingKey::from_secret(b"secret");
+        jsonwebtoken::encode(&header, &claims, &key).expect("encode the token")
+    }
+
+    async fn sleep(secs: u64) {
+        time::sleep(Duration::from_secs(secs)).await;
+    }
+
+    #[test]
+    fn cache_control() {
+        let max_age = |value| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(value));
+            max_age(&headers)
+        };
+
+        let secs = |secs| Some(Duration::from_secs(secs));
+        assert_eq!(max_age("max-age=120"), secs(120), "max-age");
+        assert_eq!(max_age("public, Max-Age=\"60\""), secs(60), "quoted");
+        assert_eq!(max_age("no-cache"), secs(0), "no-cache");
+        assert_eq!(max_age("max-age=60, no-store"), secs(0), "no-store wins");
+        assert_eq!(
+            max_age("max-age=60, max-age=10"),
+            secs(60),
+            "the first max-age"
+        );
+        assert_eq!(max_age("no-store, max-age=60"), secs(0), "no-store");
+        assert_eq!(max_age("public"), None, "no max-age");
+        assert_eq!(max_age("max-age=soon"), None, "malformed");
+        assert_eq!(super::max_age(&HeaderMap::new()), None, "no header");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_after_max_age() {
+        let provider = Provider::new(Some("public, max-age=120"));
+        let _decoder = spawn(&provider, 300).await;
+        assert_eq!(provider.fetches(), 1, "the first fetch");
+
+        sleep(119).await;
+        assert_eq!(provider.fetches(), 1, "the set is cached");
+
+        sleep(2).await;
+        assert_eq!(provider.fetches(), 2, "the set has expired");
+
+        sleep(120).await;
+        assert_eq!(provider.fetches(), 3, "the set has expired again");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_after_cooldown_if_no_cache() {
+        let provider = Provider::new(Some("no-cache"));
+        let _decoder = spawn(&provider, 300).await;
+
+        sleep(29).await;
+        assert_eq!(provider.fetches(), 1, "the cooldown");
+
+        sleep(2).await;
+        assert_eq!(provider.fetches(), 2, "refetched after the cooldown");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_after_ttl() {
+        let provider = Provider::new(None);
+        let _decoder = spawn(&provider, 60).await;
+
+        sleep(59).await;
+        assert_eq!(provider.fetches(), 1, "the set is cached");
+
+        sleep(2).await;
+        assert_eq!(provider.fetches(), 2, "the TTL has expired");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_on_unknown_kid() {
+        let provider = Provider::new(None);
+        let decoder = spawn(&provider, 300).await;
+        let decode = |kid| decoder.decode::<Value>(&token(Some(kid)));
+        assert!(decode("a").is_ok(), "the known key");
+
+        provider.rotate("b");
+        assert!(
+            matches!(decode("b"), Err(Error::UnknownKey)),
+            "the key isn't fetched yet",
+        );
+
+        sleep(29).await;
+        assert_eq!(provider.fetches(), 1, "the cooldown since the first fetch");
+
+        sleep(2).await;
+        assert_eq!(provider.fetches(), 2, "refetched on demand");
+        assert!(decode("b").is_ok(), "the new key");
+
+        provider.rotate("c");
+        for _ in 0..3 {
+            assert!(decode("c").is_err(), "the key isn't fetched yet");
+        }
+
+        sleep(28).await;
+        assert_eq!(provider.fetches(), 2, "the cooldown since the last fetch");
+
+        sleep(2).await;
+        assert_eq!(provider.fetches(), 3, "refetched once");
+        assert!(decode("c").is_ok(), "the new key");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_refresh_without_kid() {
+        let provider = Provider::new(None);
+        let decoder = spawn(&provider, 300).await;
+        assert!(
+            matches!(
+                decoder.decode::<Value>(&token(None)),
+                Err(Error::UnknownKey)
+            ),
+            "the kid is missing",
+        );
+
+        sleep(31).await;
+        assert_eq!(provider.fetches(), 1, "nothing to refetch");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_without_decoder() {
+        let provider = Provider::new(None);
+        let decoder = spawn(&provider, 60).await;
+        assert_eq!(Arc::strong_count(&provider), 2, "the task is running");
+
+        drop(decoder);
+        sleep(61).await;
+        assert_eq!(provider.fetches(), 1, "nothing is fetched");
+        assert_eq!(Arc::strong_count(&provider), 1, "the task has stopped");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn report_errors() {
+        let provider = Provider::new(None);
+        let errors = Arc::new(Mutex::new(vec![]));
+        let _decoder = Remote::new(Stub(provider.clone()))
+            .with_validation(Validation::default())
+            .with_ttl(Duration::from_secs(60))
+            .with_cooldown(Duration::from_secs(30))
+            .with_report({
+                let errors = errors.clone();
+                move |e| errors.lock().expect("lock the errors").push(e.to_string())
+            })
+            .spawn()
+            .await
+            .expect("fetch the keys");
+
+        let errors = || errors.lock().expect("lock the errors").clone();
+        provider.set_unavailable(true);
+        sleep(61).await;
+        assert_eq!(provider.fetches(), 2, "the TTL has expired");
+        assert_eq!(
+            errors(),
+            ["failed to fetch the key set: unavailable"],
+            "the error is reported",
+        );
+
+        sleep(30).await;
+        assert_eq!(provider.fetches(), 3, "retried after the cooldown");
+        assert_eq!(errors().len(), 2, "the error is reported again");
+
+        provider.set_unavailable(false);
+        sleep(30).await;
+        assert_eq!(provider.fetches(), 4, "retried after the cooldown");
+        assert_eq!(errors().len(), 2, "no error");
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn fetch_over_http() {
+        use {
+            axum::{Router, routing},
+            tokio::net::TcpListener,
+        };
+
+        let jwks = || async {
+            let jwks = r#"{ "keys": [{ "kty": "oct", "kid": "a", "k": "c2VjcmV0" }] }"#;
+            ([(header::CACHE_CONTROL, "max-age=60")], jwks)
+        };
+
+        let app = Router::new()
+            .route("/jwks", routing::get(jwks))
+            .route("/invalid", routing::get(|| async { "{}" }));
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind the listener");
+
+        let addr = listener.local_addr().expect("the local address");
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let http = |path| {
+            let url = format!("http://{addr}{path}").parse().expect("the url");
+            Http::new(url)
+        };
+
+        let res = http("/jwks").fetch().await.expect("fetch the keys");
+        assert_eq!(
+            max_age(res.headers()),
+            Some(Duration::from_secs(60)),
+            "the headers of the response",
+        );
+
+        let decoder = Remote::new(http("/jwks"))
+            .with_validation(Validation::default())
+            .spawn()
+            .await
+            .expect("fetch the keys");
+
+        assert!(
+            decoder.decode::<Value>(&token(Some("a"))).is_ok(),
+            "the fetched key",
+        );
+
+        assert!(
+            matches!(http("/missing").fetch().await, Err(HttpError::Request(_))),
+            "the error status",
+        );
+
+        assert!(
+            matches!(http("/invalid").fetch().await, Err(HttpError::Parse(_))),
+            "the invalid set",
+        );
+    }
+}
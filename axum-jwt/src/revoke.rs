@@ -0,0 +1,468 @@
+//! Token revocation.
+//!
+//! A signed token stays valid until it expires, so there is no way to log
+//! a user out or to stop a stolen token using the signature alone. The
+//! [`Revocation`] trait allows to reject tokens that are cryptographically
+//! valid, but have been revoked. Once it's set on the [decoder] with
+//! [`with_revocation`], the check is consulted by the [`Token`] and
+//! [`Claims`] extractors and by the [middleware](crate::layer) after
+//! the token is successfully decoded. A revoked token is rejected with
+//! [`Error::Revoked`].
+//!
+//! [decoder]: crate::Decoder
+//! [`with_revocation`]: crate::Decoder::with_revocation
+//! [`Token`]: crate::Token
+//! [`Claims`]: crate::Claims
+//! [`Error::Revoked`]: crate::Error::Revoked
+//!
+//! The crate ships an in-memory implementation, the [`Denylist`]. A shared
+//! store, like a database or a cache server, can be used by implementing
+//! the trait for your own type.
+//!
+//! # Examples
+//!
+//! ```
+//! use {
+//!     axum::{Router, extract::State, routing},
+//!     axum_jwt::{Claims, Decoder, jsonwebtoken::DecodingKey, revoke::{Denylist, Registered}},
+//!     std::sync::Arc,
+//! };
+//!
+//! struct App {
+//!     decoder: Decoder,
+//!     denylist: Denylist,
+//! }
+//!
+//! impl AsRef<Decoder> for App {
+//!     fn as_ref(&self) -> &Decoder {
+//!         &self.decoder
+//!     }
+//! }
+//!
+//! async fn logout(State(app): State<Arc<App>>, Claims(claims): Claims<Registered>) {
+//!     app.denylist.revoke(&claims);
+//! }
+//!
+//! let denylist = Denylist::new();
+//! let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"))
+//!     .with_revocation(denylist.clone());
+//!
+//! let app = Router::new()
+//!     .route("/logout", routing::post(logout))
+//!     .with_state(Arc::new(App { decoder, denylist }));
+//! # let _: Router = app;
+//! ```
+
+use {
+    serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned},
+    serde_json::Value,
+    std::{
+        cmp::Reverse,
+        collections::{BinaryHeap, HashMap},
+        fmt,
+        pin::Pin,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+        time::Duration,
+    },
+};
+
+/// Trait for checking if a token is revoked.
+///
+/// The check receives the [registered claims](Registered) of the token.
+/// It's only called for tokens that have been successfully decoded and
+/// validated, so the claims can be trusted.
+pub trait Revocation: Send + Sync + 'static {
+    fn is_revoked(&self, claims: &Registered) -> impl Future<Output = bool> + Send;
+}
+
+/// The object safe counterpart of the [`Revocation`] trait.
+pub(crate) trait DynRevocation: Send + Sync {
+    fn is_revoked<'a>(
+        &'a self,
+        claims: &'a Registered,
+    ) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+}
+
+impl<R> DynRevocation for R
+where
+    R: Revocation,
+{
+    fn is_revoked<'a>(
+        &'a self,
+        claims: &'a Registered,
+    ) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>> {
+        Box::pin(Revocation::is_revoked(self, claims))
+    }
+}
+
+/// Registered claims of a token.
+///
+/// Only the claims which are useful to identify a token are included.
+/// Missing claims are `None`, as well as claims of another type, like
+/// a numeric `sub` or a fractional `iat`, so the revocation check doesn't
+/// reject tokens the decoder accepts.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Registered {
+    /// The `iss` claim.
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+
+    /// The `sub` claim.
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+
+    /// The `exp` claim.
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+
+    /// The `iat` claim.
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+
+    /// The `jti` claim.
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// Deserializes the claim, treating a value of another type as missing.
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(T::deserialize(value).ok())
+}
+
+/// An in-memory denylist of revoked tokens.
+///
+/// Tokens can be revoked individually or per subject:
+///
+/// * [`revoke`](Denylist::revoke) revokes a single token by its key, which is
+///   the `jti` claim by default. A [custom key](Denylist::with_key) can be
+///   derived from the claims instead.
+/// * [`revoke_subject`](Denylist::revoke_subject) revokes all tokens of
+///   the subject issued up to now, using the `sub` and `iat` claims. This is
+///   useful to log a user out everywhere or to handle a compromised account.
+///
+/// Entries are evicted once they are no longer needed, that is, when
+/// the revoked tokens expire anyway. Since the [validation] accepts expired
+/// tokens within its leeway, the entries are kept for an additional
+/// [grace period](Denylist::with_leeway), which is 60 seconds by default,
+/// the same as the default leeway of the validation.
+///
+/// [validation]: jsonwebtoken::Validation
+///
+/// The denylist is a handle to shared state, so its clones can be used to
+/// revoke tokens while the original one is set on the [decoder](crate::Decoder).
+/// Since the state is held in memory, it's not shared between multiple
+/// instances of the application.
+#[derive(Clone)]
+pub struct Denylist {
+    state: Arc<Mutex<State>>,
+    key: fn(&Registered) -> Option<String>,
+    leeway: u64,
+}
+
+impl Denylist {
+    /// Creates an empty denylist keyed by the `jti` claim.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::default(),
+            key: |claims| claims.jti.clone(),
+            leeway: 60,
+        }
+    }
+
+    /// Sets the function to derive the token key from the claims.
+    ///
+    /// Tokens for which the function returns `None` can't be revoked
+    /// individually.
+    ///
+    /// # Examples
+    ///
+    /// Use the issuer together with the token identifier as a key:
+    ///
+    /// ```
+    /// use axum_jwt::revoke::Denylist;
+    ///
+    /// let denylist = Denylist::new().with_key(|claims| {
+    ///     let iss = claims.iss.as_deref()?;
+    ///     let jti = claims.jti.as_deref()?;
+    ///     Some(format!("{iss}:{jti}"))
+    /// });
+    /// ```
+    pub fn with_key(mut self, key: fn(&Registered) -> Option<String>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Sets the grace period for which entries are kept after the revoked
+    /// tokens expire.
+    ///
+    /// The period must be at least the [leeway] of the validation used to
+    /// decode the tokens, otherwise a revoked token becomes valid again
+    /// after its expiration until the leeway passes.
+    ///
+    /// [leeway]: jsonwebtoken::Validation::leeway
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway.as_secs();
+        self
+    }
+
+    /// Revokes the token with the provided claims.
+    ///
+    /// The entry is kept until the token expires and the grace period
+    /// passes. If the token has no `exp` claim, the entry is kept forever.
+    /// If no key can be derived from the claims, this method does nothing.
+    pub fn revoke(&self, claims: &Registered) {
+        if let Some(key) = (self.key)(claims) {
+            self.revoke_key(key, claims.exp);
+        }
+    }
+
+    /// Revokes the token with the provided key until the `exp` timestamp.
+    pub fn revoke_key<K>(&self, key: K, exp: Option<u64>)
+    where
+        K: Into<String>,
+    {
+        let key = key.into();
+        let mut state = self.lock();
+        let until = match state.keys.get(&key) {
+            Some(&prev) => prev.zip(exp).map(|(prev, exp)| prev.max(exp)),
+            None => exp,
+        };
+
+        state.keys.insert(key.clone(), until);
+        if let Some(exp) = until {
+            state.expiry.push(Reverse((exp, Entry::Key(key))));
+        }
+    }
+
+    /// Revokes all tokens of the subject issued before now.
+    ///
+    /// A token is revoked if its `iat` claim is earlier than the current
+    /// time. Tokens without the `iat` claim are revoked as well. Tokens
+    /// issued after this call are accepted.
+    ///
+    /// The `iat` claim has a granularity of one second, so tokens issued
+    /// within the same second as this call are accepted, even if they were
+    /// issued just before it. This way a token issued right after
+    /// the revocation, for example, on the next login, is never rejected.
+    /// To reject a particular token of the current second, revoke it with
+    /// [`revoke`](Denylist::revoke).
+    ///
+    /// The `lifetime` is the maximum lifetime of the tokens. After this
+    /// time and the grace period, all revoked tokens are expired and
+    /// the entry is evicted.
+    pub fn revoke_subject<S>(&self, sub: S, lifetime: Duration)
+    where
+        S: Into<String>,
+    {
+        let sub = sub.into();
+        let now = jsonwebtoken::get_current_timestamp();
+        let exp = now.saturating_add(lifetime.as_secs());
+        let mut state = self.lock();
+        let entry = state.subjects.entry(sub.clone()).or_insert((now, exp));
+        *entry = (now, exp.max(entry.1));
+        state.expiry.push(Reverse((exp, Entry::Subject(sub))));
+    }
+
+    /// Checks if the token with the provided claims is revoked.
+    pub fn is_revoked(&self, claims: &Registered) -> bool {
+        let state = self.lock();
+
+        if let Some(key) = (self.key)(claims)
+            && state.keys.contains_key(&key)
+        {
+            return true;
+        }
+
+        if let Some(sub) = &claims.sub
+            && let Some(&(issued, _)) = state.subjects.get(sub)
+        {
+            return claims.iat.is_none_or(|iat| iat < issued);
+        }
+
+        false
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = jsonwebtoken::get_current_timestamp();
+        state.evict(now.saturating_sub(self.leeway));
+        state
+    }
+}
+
+impl Default for Denylist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Revocation for Denylist {
+    async fn is_revoked(&self, claims: &Registered) -> bool {
+        Self::is_revoked(self, claims)
+    }
+}
+
+impl fmt::Debug for Denylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Denylist")
+            .field("keys", &state.keys.len())
+            .field("subjects", &state.subjects.len())
+            .field("leeway", &self.leeway)
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct State {
+    keys: HashMap<String, Option<u64>>,
+    subjects: HashMap<String, (u64, u64)>,
+    expiry: BinaryHeap<Reverse<(u64, Entry)>>,
+}
+
+impl State {
+    /// Removes the entries of tokens expired before the provided time.
+    fn evict(&mut self, before: u64) {
+        while let Some(Reverse((exp, _))) = self.expiry.peek()
+            && *exp < before
+        {
+            let Some(Reverse((exp, entry))) = self.expiry.pop() else {
+                break;
+            };
+
+            // The entry could be revoked again with a later expiration,
+            // in which case it's still needed
+            match entry {
+                Entry::Key(key) => {
+                    if self.keys.get(&key) == Some(&Some(exp)) {
+                        self.keys.remove(&key);
+                    }
+                }
+                Entry::Subject(sub) => {
+                    if self
+                        .subjects
+                        .get(&sub)
+                        .is_some_and(|&(_, until)| until == exp)
+                    {
+                        self.subjects.remove(&sub);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Entry {
+    Key(String),
+    Subject(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(jti: &str, exp: u64) -> Registered {
+        Registered {
+            jti: Some(jti.to_owned()),
+            exp: Some(exp),
+            ..Registered::default()
+        }
+    }
+
+    #[test]
+    fn keep_within_leeway() {
+        let now = jsonwebtoken::get_current_timestamp();
+        let denylist = Denylist::new();
+        let recent = claims("recent", now - 30);
+        let old = claims("old", now - 120);
+        denylist.revoke(&recent);
+        denylist.revoke(&old);
+        assert!(
+            denylist.is_revoked(&recent),
+            "the token is within the leeway"
+        );
+        assert!(!denylist.is_revoked(&old), "the token is beyond the leeway");
+    }
+
+    #[test]
+    fn custom_leeway() {
+        let now = jsonwebtoken::get_current_timestamp();
+        let denylist = Denylist::new().with_leeway(Duration::ZERO);
+        let expired = claims("expired", now - 30);
+        let active = claims("active", now + 30);
+        denylist.revoke(&expired);
+        denylist.revoke(&active);
+        assert!(!denylist.is_revoked(&expired), "the entry must be evicted");
+        assert!(denylist.is_revoked(&active), "the token is revoked");
+    }
+
+    #[test]
+    fn revoke_subject() {
+        let now = jsonwebtoken::get_current_timestamp();
+        let denylist = Denylist::new();
+        denylist.revoke_subject("alice", Duration::from_secs(60));
+
+        let issued = Registered {
+            sub: Some("alice".to_owned()),
+            iat: Some(now - 10),
+            ..Registered::default()
+        };
+
+        let later = Registered {
+            iat: Some(now + 10),
+            ..issued.clone()
+        };
+
+        assert!(denylist.is_revoked(&issued), "the token is issued before");
+        assert!(!denylist.is_revoked(&later), "the token is issued after");
+    }
+
+    #[test]
+    fn revoke_subject_same_second() {
+        let denylist = Denylist::new();
+        denylist.revoke_subject("alice", Duration::from_secs(60));
+        let issued = {
+            let state = denylist.lock();
+            state.subjects["alice"].0
+        };
+
+        let claims = |iat| Registered {
+            sub: Some("alice".to_owned()),
+            iat: Some(iat),
+            ..Registered::default()
+        };
+
+        assert!(
+            denylist.is_revoked(&claims(issued - 1)),
+            "the previous second",
+        );
+
+        assert!(
+            !denylist.is_revoked(&claims(issued)),
+            "the token is issued in the same second",
+        );
+    }
+
+    #[test]
+    fn evict_revoked_again() {
+        let mut state = State::default();
+        for exp in [10, 20] {
+            state.keys.insert("key".to_owned(), Some(exp));
+            state
+                .expiry
+                .push(Reverse((exp, Entry::Key("key".to_owned()))));
+        }
+
+        state.evict(15);
+        assert!(state.keys.contains_key("key"), "the key is revoked again");
+        state.evict(21);
+        assert!(state.keys.is_empty(), "all entries must be evicted");
+    }
+}
@@ -0,0 +1,175 @@
+//! Per-route access policies.
+//!
+//! Instead of applying a separately configured [middleware](crate::layer) to
+//! each group of routes, a single layer at the top of the router can enforce
+//! a table of [routes](Routes). Each route is mapped to its
+//! [access](Access) policy: the route is either public, accepts anonymous
+//! requests, requires authentication or additionally requires an
+//! [authorization requirement](crate::authz). To apply the table, pass it to
+//! the [`with_routes`](crate::layer::JwtLayer::with_routes) method.
+//!
+//! The routes are identified by their paths exactly as they were registered in
+//! the router, like `/orders/{id}`, so the table must be applied with
+//! [`Router::layer`] to see the [matched path](axum::extract::MatchedPath). The paths of
+//! routes in [nested](https://docs.rs/axum/latest/axum/struct.Router.html#method.nest)
+//! routers include the prefix. Requests that don't match any route, for
+//! example, the ones handled by the router fallback, use the
+//! [fallback](Routes::with_fallback) policy.
+//!
+//! This module requires the `routes` feature.
+//!
+//! [`Router::layer`]: https://docs.rs/axum/latest/axum/struct.Router.html#method.layer
+//!
+//! # Examples
+//!
+//! ```
+//! use {
+//!     axum::{Router, routing},
+//!     axum_jwt::{Decoder, authz, jsonwebtoken::DecodingKey, routes::Routes},
+//! };
+//!
+//! let routes = Routes::new()
+//!     .public("/health")
+//!     .public("/docs/{*path}")
+//!     .require("/orders/{id}", authz::require_scopes(["read:orders"]))
+//!     .require("/admin/{*path}", authz::require_any_role(["admin"]));
+//!
+//! let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+//!
+//! # async fn handler() {}
+//! let app = Router::new()
+//!     .route("/health", routing::get(handler))
+//!     .route("/me", routing::get(handler))
+//!     .route("/orders/{id}", routing::get(handler))
+//!     .layer(axum_jwt::layer(decoder).with_routes(routes));
+//! # let _: Router = app;
+//! ```
+
+use {
+    crate::authz::Require,
+    std::{borrow::Cow, collections::HashMap},
+};
+
+/// The access policy of a route.
+#[derive(Clone, Debug)]
+pub enum Access {
+    /// The route is public, the middleware passes requests through
+    /// without authentication.
+    Public,
+
+    /// The route accepts anonymous requests in
+    /// the [optional](crate::layer::JwtLayer::with_optional) mode of
+    /// the layer, or in the [strict](crate::Optional::Strict) one if
+    /// the mode isn't set.
+    Optional,
+
+    /// The route requires a valid token.
+    ///
+    /// Anonymous requests are rejected even if the layer is in
+    /// the [optional](crate::layer::JwtLayer::with_optional) mode.
+    Authenticated,
+
+    /// The route requires a valid token satisfying the requirement.
+    ///
+    /// Like [`Access::Authenticated`], anonymous requests are always
+    /// rejected.
+    Require(Require),
+}
+
+/// A table of routes and their [access](Access) policies.
+///
+/// Routes that aren't in the table use the fallback policy, which is
+/// [`Access::Authenticated`] by default.
+#[derive(Clone, Debug)]
+pub struct Routes {
+    routes: HashMap<Cow<'static, str>, Access>,
+    fallback: Access,
+}
+
+impl Routes {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self {
+            routes: HashMap::new(),
+            fallback: Access::Authenticated,
+        }
+    }
+
+    /// Adds a route with the access policy.
+    ///
+    /// The path must be the same as the one passed to the router, if
+    /// the route is already in the table, its policy is replaced.
+    pub fn route<P>(mut self, path: P, access: Access) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        self.routes.insert(path.into(), access);
+        self
+    }
+
+    /// Adds a public route.
+    pub fn public<P>(self, path: P) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        self.route(path, Access::Public)
+    }
+
+    /// Adds a route accepting anonymous requests.
+    pub fn optional<P>(self, path: P) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        self.route(path, Access::Optional)
+    }
+
+    /// Adds a route requiring authentication.
+    pub fn authenticated<P>(self, path: P) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        self.route(path, Access::Authenticated)
+    }
+
+    /// Adds a route requiring the authorization requirement.
+    pub fn require<P>(self, path: P, require: Require) -> Self
+    where
+        P: Into<Cow<'static, str>>,
+    {
+        self.route(path, Access::Require(require))
+    }
+
+    /// Sets the policy for routes that aren't in the table.
+    ///
+    /// # Examples
+    ///
+    /// Make all routes public except the listed ones:
+    ///
+    /// ```
+    /// use axum_jwt::routes::{Access, Routes};
+    ///
+    /// let routes = Routes::new()
+    ///     .authenticated("/me")
+    ///     .with_fallback(Access::Public);
+    /// ```
+    pub fn with_fallback(mut self, fallback: Access) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Returns the access policy for the route.
+    ///
+    /// The `route` is the [matched path](axum::extract::MatchedPath) of the request, if
+    /// there is none, the fallback policy is returned.
+    pub fn access(&self, route: Option<&str>) -> &Access {
+        route
+            .and_then(|route| self.routes.get(route))
+            .unwrap_or(&self.fallback)
+    }
+}
+
+impl Default for Routes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,155 @@
+use {
+    crate::{
+        challenge::{Challenge, Render},
+        error::{Error, Rejection},
+        extract::Optional,
+    },
+    axum_core::response::{IntoResponse, Response},
+    jsonwebtoken::TokenData,
+    serde::de::DeserializeOwned,
+    serde_json::Value,
+    std::sync::Arc,
+};
+
+/// Trait for token verification.
+///
+/// Both the extractors and the [middleware](crate::layer) are generic over
+/// the verifier, the default one is the JWT [decoder](crate::Decoder).
+/// Implement this trait to validate tokens another way, for example, with
+/// a key stored in a KMS, or to accept fixed tokens in tests.
+///
+/// The verifier returns the token [header](jsonwebtoken::Header) and claims.
+/// Tokens of other formats have no JWT header, so the verifier may return
+/// the default one. The claims are then deserialized into the type requested
+/// by the extractor or the middleware. A verifier which can decode the claims
+/// into that type directly may also implement
+/// [`verify_as`](Verifier::verify_as) to skip the intermediate [`Value`].
+///
+/// # Examples
+///
+/// A verifier accepting a fixed token:
+///
+/// ```
+/// use {
+///     axum::{Router, routing},
+///     axum_jwt::{
+///         Bearer, ClaimsWith, Error, Verifier,
+///         jsonwebtoken::{Header, TokenData},
+///     },
+///     serde::Deserialize,
+///     serde_json::{Value, json},
+/// };
+///
+/// #[derive(Clone)]
+/// struct Fixed;
+///
+/// impl Verifier for Fixed {
+///     async fn verify(&self, token: &str) -> Result<TokenData<Value>, Error> {
+///         if token == "test" {
+///             let claims = json!({ "sub": "tester" });
+///             Ok(TokenData { header: Header::default(), claims })
+///         } else {
+///             Err(Error::Signature)
+///         }
+///     }
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn hello(ClaimsWith { claims: u, .. }: ClaimsWith<User, Bearer, Fixed>) -> String {
+///     format!("Hello, {}!", u.sub)
+/// }
+///
+/// let app = Router::new()
+///     .route("/", routing::get(hello))
+///     .with_state(Fixed);
+/// # let _: Router = app;
+/// ```
+pub trait Verifier: Clone + Send + Sync + 'static {
+    /// Verifies the token and returns its header and claims.
+    fn verify(&self, token: &str) -> impl Future<Output = Result<TokenData<Value>, Error>> + Send;
+
+    /// Verifies the token and deserializes its claims into the requested
+    /// type.
+    ///
+    /// The extractors call this method when the token hasn't been verified
+    /// by the [middleware](crate::layer). By default, deserializes
+    /// the claims returned by [`verify`](Verifier::verify).
+    fn verify_as<T>(&self, token: &str) -> impl Future<Output = Result<TokenData<T>, Error>> + Send
+    where
+        T: DeserializeOwned,
+    {
+        async move { claims(&self.verify(token).await?) }
+    }
+
+    /// Verifies the token without waiting, if it's possible.
+    ///
+    /// The [middleware](crate::layer) calls this method first and awaits
+    /// the [`verify`](Verifier::verify) future only if it returns `None`,
+    /// so the requests are handled without allocating it. By default,
+    /// returns `None`.
+    fn verify_now(&self, token: &str) -> Option<Result<TokenData<Value>, Error>> {
+        _ = token;
+        None
+    }
+
+    /// Returns the [renderer](Render) of authentication errors.
+    ///
+    /// By default, errors are rendered with the [RFC 6750](Challenge)
+    /// challenge.
+    fn render(&self) -> Arc<dyn Render> {
+        Arc::new(Challenge::default())
+    }
+
+    /// Returns how optional extractors treat invalid tokens.
+    ///
+    /// The default is [`Optional::Strict`].
+    fn optional(&self) -> Optional {
+        Optional::Strict
+    }
+
+    /// Checks if both verifiers validate tokens the same way.
+    ///
+    /// If so, the extractors reuse the token validated by the middleware
+    /// instead of verifying it again. By default, returns `false`.
+    fn is_same(&self, other: &Self) -> bool {
+        _ = other;
+        false
+    }
+}
+
+/// Renders the error into a response.
+pub(crate) fn reject<V, U>(verifier: &V, error: Error<U>) -> Response
+where
+    V: Verifier,
+    U: IntoResponse,
+{
+    verifier
+        .render()
+        .render(error.map(IntoResponse::into_response))
+}
+
+/// Creates the extractor rejection.
+pub(crate) fn rejection<V>(verifier: &V, error: Error) -> Rejection
+where
+    V: Verifier,
+{
+    Rejection::new(error, verifier.render())
+}
+
+/// Deserializes the verified claims into the requested type.
+pub(crate) fn claims<T>(data: &TokenData<Value>) -> Result<TokenData<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    match T::deserialize(&data.claims) {
+        Ok(claims) => Ok(TokenData {
+            header: data.header.clone(),
+            claims,
+        }),
+        Err(e) => Err(jsonwebtoken::errors::Error::from(e).into()),
+    }
+}
@@ -0,0 +1,163 @@
+use {
+    axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode, header},
+        response::Response,
+        routing,
+    },
+    axum_jwt::{
+        Decoder, Encoder,
+        authz::{self, Authorized, Require, RequireScope, Requirement, ScopeSpec},
+        jsonwebtoken::{DecodingKey, EncodingKey},
+    },
+    serde::Deserialize,
+    serde_json::{Value, json},
+    tower::ServiceExt,
+};
+
+#[derive(Deserialize)]
+struct User {
+    sub: String,
+}
+
+struct ReadOrders;
+
+impl ScopeSpec for ReadOrders {
+    const SCOPES: &'static [&'static str] = &["read:orders"];
+}
+
+struct Admin;
+
+impl ScopeSpec for Admin {
+    const CLAIM: &'static str = "realm_access.roles";
+    const SCOPES: &'static [&'static str] = &["admin", "auditor"];
+}
+
+struct Manager;
+
+impl Requirement for Manager {
+    fn require() -> Require {
+        authz::require_any([
+            authz::require_scopes(["write:orders"]),
+            authz::require_any_role(["manager"]).with_claim("realm_access.roles"),
+        ])
+    }
+}
+
+fn decoder() -> Decoder {
+    Decoder::from_key(DecodingKey::from_secret(b"secret"))
+}
+
+fn token(claims: &Value) -> String {
+    let encoder = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+    encoder.encode(claims).expect("encode the token")
+}
+
+async fn send(app: Router, path: &str, claims: Option<Value>) -> Response {
+    let mut req = Request::get(path);
+    if let Some(claims) = claims {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {}", token(&claims)));
+    }
+
+    let req = req.body(Body::empty()).expect("build the request");
+    app.oneshot(req).await.expect("call the router")
+}
+
+fn app() -> Router {
+    async fn orders(RequireScope(u, _): RequireScope<ReadOrders, User>) -> String {
+        u.sub
+    }
+
+    async fn manage(Authorized(u, _): Authorized<Manager, User>) -> String {
+        u.sub
+    }
+
+    async fn admin(_: RequireScope<Admin>) {}
+
+    Router::new()
+        .route("/orders", routing::get(orders))
+        .route("/admin", routing::get(admin))
+        .route("/manage", routing::get(manage))
+        .with_state(decoder())
+}
+
+fn assert_insufficient_scope(res: &Response) {
+    assert_eq!(res.status(), StatusCode::FORBIDDEN, "the status");
+    let challenge = res
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .expect("the challenge");
+
+    assert!(
+        challenge.starts_with("Bearer ") && challenge.contains(r#"error="insufficient_scope""#),
+        "the error code in {challenge}",
+    );
+}
+
+#[tokio::test]
+async fn require_scope() {
+    let claims = json!({ "sub": "alice", "scope": "read:orders write:orders" });
+    let res = send(app(), "/orders", Some(claims)).await;
+    assert_eq!(res.status(), StatusCode::OK, "the scope is granted");
+
+    let claims = json!({ "sub": "alice", "scope": "write:orders" });
+    let res = send(app(), "/orders", Some(claims)).await;
+    assert_insufficient_scope(&res);
+
+    let res = send(app(), "/orders", None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "no token");
+}
+
+#[tokio::test]
+async fn require_scope_claim() {
+    let claims = json!({ "realm_access": { "roles": ["auditor", "admin"] } });
+    let res = send(app(), "/admin", Some(claims)).await;
+    assert_eq!(res.status(), StatusCode::OK, "the roles are granted");
+
+    let claims = json!({ "realm_access": { "roles": "admin auditor" } });
+    let res = send(app(), "/admin", Some(claims)).await;
+    assert_eq!(res.status(), StatusCode::OK, "the space-delimited roles");
+
+    let claims = json!({ "realm_access": { "roles": ["admin"] } });
+    let res = send(app(), "/admin", Some(claims)).await;
+    assert_insufficient_scope(&res);
+
+    let claims = json!({ "roles": ["admin", "auditor"] });
+    let res = send(app(), "/admin", Some(claims)).await;
+    assert_insufficient_scope(&res);
+}
+
+#[tokio::test]
+async fn authorized() {
+    let claims = json!({ "sub": "alice", "scope": "write:orders" });
+    let res = send(app(), "/manage", Some(claims)).await;
+    assert_eq!(res.status(), StatusCode::OK, "the scope is granted");
+
+    let claims = json!({ "sub": "bob", "realm_access": { "roles": ["manager"] } });
+    let res = send(app(), "/manage", Some(claims)).await;
+    assert_eq!(res.status(), StatusCode::OK, "the role is granted");
+
+    let claims = json!({ "sub": "eve", "roles": ["manager"], "scope": "read:orders" });
+    let res = send(app(), "/manage", Some(claims)).await;
+    assert_insufficient_scope(&res);
+
+    let res = send(app(), "/manage", None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "no token");
+}
+
+#[tokio::test]
+async fn layer_require() {
+    let app = Router::new()
+        .route("/", routing::get(|| async {}))
+        .layer(axum_jwt::layer(decoder()).with_require(Manager::require()));
+
+    let claims = json!({ "sub": "alice", "scope": "write:orders" });
+    let res = send(app.clone(), "/", Some(claims)).await;
+    assert_eq!(res.status(), StatusCode::OK, "the requirement is satisfied");
+
+    let claims = json!({ "sub": "alice", "scope": "read:orders" });
+    let res = send(app, "/", Some(claims)).await;
+    assert_insufficient_scope(&res);
+}
@@ -0,0 +1,159 @@
+use {
+    axum::{
+        Extension, Router,
+        body::{self, Body},
+        http::{Request, Response, StatusCode, Uri, header},
+        routing,
+    },
+    axum_jwt::{
+        Bearer, ClaimsWith, Decoder, Encoder, Token,
+        introspect::{Fallback, Introspector, Post},
+        jsonwebtoken::{DecodingKey, EncodingKey},
+    },
+    serde::Deserialize,
+    serde_json::json,
+    std::{
+        convert::Infallible,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    },
+    tower::ServiceExt,
+};
+
+#[derive(Clone, Deserialize)]
+struct User {
+    sub: String,
+}
+
+/// The authorization server, which knows the opaque tokens of alice and bob.
+struct Server {
+    posts: Arc<AtomicUsize>,
+}
+
+impl Post for Server {
+    type Error = Infallible;
+
+    async fn post(&self, req: Request<String>) -> Result<Response<Vec<u8>>, Self::Error> {
+        self.posts.fetch_add(1, Ordering::Relaxed);
+        let body = match req.body().strip_prefix("token=") {
+            Some(form) if form.starts_with("alice&") => json!({ "active": true, "sub": "alice" }),
+            Some(form) if form.starts_with("bob&") => json!({ "active": true, "sub": "bob" }),
+            _ => json!({ "active": false }),
+        };
+
+        Ok(Response::new(body.to_string().into_bytes()))
+    }
+}
+
+/// Creates the introspector, which doesn't cache the tokens without `exp`,
+/// so every verification is posted.
+fn introspector() -> (Introspector, Arc<AtomicUsize>) {
+    let posts = Arc::new(AtomicUsize::new(0));
+    let server = Server {
+        posts: posts.clone(),
+    };
+
+    let endpoint = Uri::from_static("https://auth.example.com/introspect");
+    (Introspector::new(endpoint, server), posts)
+}
+
+fn bearer(token: &str) -> Request<Body> {
+    Request::get("/")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .expect("build the request")
+}
+
+async fn text(app: Router, req: Request<Body>) -> (StatusCode, String) {
+    let res = app.oneshot(req).await.expect("call the router");
+    let status = res.status();
+    let body = body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("read the body");
+
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+#[tokio::test]
+async fn introspector_layer() {
+    async fn hello(
+        Extension(t): Extension<Token<User>>,
+        ClaimsWith { claims: u, .. }: ClaimsWith<User, Bearer, Introspector>,
+    ) -> String {
+        format!("{} {}", t.claims.sub, u.sub)
+    }
+
+    let (introspector, posts) = introspector();
+    let app = Router::new()
+        .route("/", routing::get(hello))
+        .layer(
+            axum_jwt::layer(introspector.clone())
+                .with_filter(|t: &Token<User>| t.claims.sub == "alice")
+                .store_to_extension(),
+        )
+        .with_state(introspector);
+
+    assert_eq!(
+        text(app.clone(), bearer("alice")).await,
+        (StatusCode::OK, "alice alice".to_owned()),
+        "the token is introspected",
+    );
+
+    assert_eq!(
+        posts.load(Ordering::Relaxed),
+        1,
+        "the extractor reuses the token introspected by the middleware",
+    );
+
+    let (status, _) = text(app.clone(), bearer("bob")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the filter rejects");
+
+    let (status, _) = text(app, bearer("eve")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the token is inactive");
+    assert_eq!(posts.load(Ordering::Relaxed), 3, "each token is posted");
+}
+
+#[tokio::test]
+async fn fallback_layer() {
+    async fn hello(ClaimsWith { claims: u, .. }: ClaimsWith<User, Bearer, Fallback>) -> String {
+        u.sub
+    }
+
+    let (introspector, posts) = introspector();
+    let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    let fallback = Fallback::new(decoder, introspector);
+    let app = Router::new()
+        .route("/", routing::get(hello))
+        .layer(axum_jwt::layer(fallback.clone()))
+        .with_state(fallback);
+
+    let encoder = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+    let jwt = encoder
+        .encode(&json!({ "sub": "carol" }))
+        .expect("encode the token");
+
+    assert_eq!(
+        text(app.clone(), bearer(&jwt)).await,
+        (StatusCode::OK, "carol".to_owned()),
+        "the JWT is decoded",
+    );
+
+    assert_eq!(posts.load(Ordering::Relaxed), 0, "the JWT isn't posted");
+
+    assert_eq!(
+        text(app.clone(), bearer("bob")).await,
+        (StatusCode::OK, "bob".to_owned()),
+        "the opaque token is introspected",
+    );
+
+    assert_eq!(posts.load(Ordering::Relaxed), 1, "posted once");
+
+    let (status, _) = text(app.clone(), bearer("eve")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the token is inactive");
+
+    let (status, _) = text(app, bearer("a.b.c")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the malformed JWT");
+    assert_eq!(posts.load(Ordering::Relaxed), 2, "the JWT isn't posted");
+}
@@ -0,0 +1,910 @@
+use {
+    axum::{
+        Extension, Router,
+        body::{self, Body},
+        http::{HeaderName, Request, StatusCode, Uri, header, request::Parts},
+        response::{IntoResponse, Response},
+        routing,
+    },
+    axum_jwt::{
+        Claims, ClaimsWith, Cookie, Csrf, Decoder, Encoder, Error, Extract, FromState, Optional,
+        Protocol, Query, Raw, Subprotocol, Token, Verifier,
+        jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData},
+    },
+    serde::{Deserialize, Serialize},
+    serde_json::{Value, json},
+    std::{
+        borrow::Cow,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    },
+    tower::ServiceExt,
+};
+
+#[derive(Clone, Deserialize, Serialize)]
+struct User {
+    sub: String,
+}
+
+fn decoder() -> Decoder {
+    Decoder::from_key(DecodingKey::from_secret(b"secret"))
+}
+
+fn token() -> String {
+    encode("alice")
+}
+
+fn encode(sub: &str) -> String {
+    let encoder = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+    let user = User {
+        sub: sub.to_owned(),
+    };
+
+    encoder.encode(&user).expect("encode the token")
+}
+
+async fn send(app: Router, req: Request<Body>) -> StatusCode {
+    app.oneshot(req).await.expect("call the router").status()
+}
+
+async fn text(app: Router, req: Request<Body>) -> (StatusCode, String) {
+    let res = app.oneshot(req).await.expect("call the router");
+    let status = res.status();
+    let body = body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("read the body");
+
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+fn get() -> axum::http::request::Builder {
+    Request::get("/")
+}
+
+fn bearer(uri: &str, token: &str) -> Request<Body> {
+    Request::get(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .expect("build the request")
+}
+
+struct Named {
+    header: HeaderName,
+}
+
+impl Default for Named {
+    fn default() -> Self {
+        Self {
+            header: HeaderName::from_static("x-auth-token"),
+        }
+    }
+}
+
+impl Extract for Named {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let value = parts.headers.get(&self.header).ok_or(Error::Missing)?;
+        value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
+    }
+}
+
+async fn alice(Token { claims: u, .. }: Token<User, Named>) -> StatusCode {
+    if u.sub == "alice" {
+        StatusCode::OK
+    } else {
+        StatusCode::FORBIDDEN
+    }
+}
+
+#[tokio::test]
+async fn extractor_from_layer() {
+    let named = Named {
+        header: HeaderName::from_static("x-session"),
+    };
+
+    let app = Router::new()
+        .route("/", routing::get(alice))
+        .layer(axum_jwt::layer(decoder()).with_extract(named))
+        .with_state(decoder());
+
+    let req = get()
+        .header("x-session", token())
+        .body(Default::default())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app, req).await,
+        StatusCode::OK,
+        "the handler uses the header name of the layer",
+    );
+}
+
+#[tokio::test]
+async fn default_extractor_without_layer() {
+    let app = Router::new()
+        .route("/", routing::get(alice))
+        .with_state(decoder());
+
+    let req = get()
+        .header("x-session", token())
+        .body(Default::default())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app.clone(), req).await,
+        StatusCode::UNAUTHORIZED,
+        "the default header name is used",
+    );
+
+    let req = get()
+        .header("x-auth-token", token())
+        .body(Default::default())
+        .expect("build the request");
+
+    assert_eq!(send(app, req).await, StatusCode::OK, "the default header");
+}
+
+#[tokio::test]
+async fn claims_with_extractor() {
+    async fn hello(ClaimsWith { claims: u, .. }: ClaimsWith<User, Cookie>) -> String {
+        u.sub
+    }
+
+    let app = Router::new()
+        .route("/", routing::get(hello))
+        .layer(axum_jwt::layer(decoder()).with_extract(Cookie::new("session")))
+        .with_state(decoder());
+
+    let req = get()
+        .header(header::COOKIE, format!("session={}", token()))
+        .body(Body::empty())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app.clone(), req).await,
+        StatusCode::OK,
+        "the handler uses the cookie name of the layer",
+    );
+
+    let req = get()
+        .header(header::COOKIE, format!("access_token={}", token()))
+        .body(Body::empty())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app, req).await,
+        StatusCode::UNAUTHORIZED,
+        "the default cookie name isn't used",
+    );
+}
+
+#[tokio::test]
+async fn raw_token() {
+    async fn forward(Raw { token, claims, .. }: Raw<User, Named>) -> String {
+        format!("{} {token}", claims.sub)
+    }
+
+    let app = Router::new()
+        .route("/", routing::get(forward))
+        .with_state(decoder());
+
+    let encoded = token();
+    let req = get()
+        .header("x-auth-token", &encoded)
+        .body(Body::empty())
+        .expect("build the request");
+
+    let res = app.clone().oneshot(req).await.expect("call the router");
+    assert_eq!(res.status(), StatusCode::OK, "the token is accepted");
+    let body = body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("read the body");
+
+    assert_eq!(
+        body,
+        format!("alice {encoded}").as_bytes(),
+        "the claims and the encoded token",
+    );
+
+    let req = get()
+        .header("x-auth-token", "invalid")
+        .body(Body::empty())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app, req).await,
+        StatusCode::UNAUTHORIZED,
+        "the token is verified",
+    );
+}
+
+struct Custom {
+    header: HeaderName,
+}
+
+impl Extract for Custom {
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let value = parts.headers.get(&self.header).ok_or(Error::Missing)?;
+        value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
+    }
+}
+
+struct App {
+    decoder: Decoder,
+    header: HeaderName,
+}
+
+impl AsRef<Decoder> for App {
+    fn as_ref(&self) -> &Decoder {
+        &self.decoder
+    }
+}
+
+impl FromState<Arc<App>> for Custom {
+    fn from_state(app: &Arc<App>) -> Self {
+        Self {
+            header: app.header.clone(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn extractor_from_state() {
+    async fn hello(Token { claims: u, .. }: Token<User, Custom>) -> String {
+        u.sub
+    }
+
+    let state = App {
+        decoder: decoder(),
+        header: HeaderName::from_static("x-auth-token"),
+    };
+
+    let app = Router::new()
+        .route("/", routing::get(hello))
+        .with_state(Arc::new(state));
+
+    let req = get()
+        .header("x-auth-token", token())
+        .body(Default::default())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app, req).await,
+        StatusCode::OK,
+        "the header name is taken from the state",
+    );
+}
+
+#[tokio::test]
+async fn async_filter() {
+    let check = |t: &Token<User>| {
+        let sub = t.claims.sub.clone();
+        async move {
+            tokio::task::yield_now().await;
+            sub == "alice"
+        }
+    };
+
+    let app = Router::new()
+        .route("/", routing::get(async || {}))
+        .layer(axum_jwt::layer(decoder()).with_async_filter(check));
+
+    let status = send(app.clone(), bearer("/", &token())).await;
+    assert_eq!(status, StatusCode::OK, "the filter passes");
+
+    let status = send(app.clone(), bearer("/", &encode("bob"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the filter rejects");
+
+    let status = send(app, bearer("/", "invalid")).await;
+    assert_eq!(
+        status,
+        StatusCode::UNAUTHORIZED,
+        "the filter isn't called for an invalid token",
+    );
+}
+
+#[tokio::test]
+async fn selected_protocol() {
+    async fn connect(
+        Subprotocol {
+            protocol, claims, ..
+        }: Subprotocol<User>,
+    ) -> String {
+        format!("{protocol} {}", claims.sub)
+    }
+
+    let app = Router::new()
+        .route("/", routing::get(connect))
+        .layer(axum_jwt::layer(decoder()).with_extract(Protocol::new("auth")))
+        .with_state(decoder());
+
+    let req = get()
+        .header(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            format!("chat, auth, {}", token()),
+        )
+        .body(Body::empty())
+        .expect("build the request");
+
+    let res = app.oneshot(req).await.expect("call the router");
+    assert_eq!(res.status(), StatusCode::OK, "the token is accepted");
+    let body = body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("read the body");
+
+    assert_eq!(&body[..], b"auth alice", "the configured marker");
+}
+
+#[tokio::test]
+async fn encoded_query() {
+    async fn hello(Token { claims: u, .. }: Token<User, Query>) -> String {
+        u.sub
+    }
+
+    let app = Router::new()
+        .route("/", routing::get(hello))
+        .layer(axum_jwt::layer(decoder()).with_extract(Query::default()))
+        .with_state(decoder());
+
+    let token = token().replace('.', "%2E");
+    let req = Request::get(format!("/?access_token={token}"))
+        .body(Body::empty())
+        .expect("build the request");
+
+    assert_eq!(
+        send(app, req).await,
+        StatusCode::OK,
+        "the token is percent-decoded",
+    );
+}
+
+/// The decoder counting verified tokens.
+#[derive(Clone)]
+struct Counting {
+    decoder: Decoder,
+    calls: Arc<AtomicUsize>,
+}
+
+impl Counting {
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+impl Verifier for Counting {
+    async fn verify(&self, token: &str) -> Result<TokenData<Value>, Error> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.decoder.verify(token).await
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.calls, &other.calls)
+    }
+}
+
+#[tokio::test]
+async fn stripped_query() {
+    async fn claims(ClaimsWith { claims: u, .. }: ClaimsWith<User, Query, Counting>) -> String {
+        u.sub
+    }
+
+    async fn typed(Token { claims: u, .. }: Token<User, Query, Counting>) -> String {
+        u.sub
+    }
+
+    async fn raw(Raw { token, .. }: Raw<User, Query, Counting>, uri: Uri) -> String {
+        assert_eq!(uri.query(), Some("a=1"), "the token is stripped");
+        token
+    }
+
+    // The extractors reuse the token only if it's verified by the same verifier
+    let counting = Counting {
+        decoder: decoder(),
+        calls: Arc::default(),
+    };
+
+    let app = Router::new()
+        .route("/claims", routing::get(claims))
+        .route("/token", routing::get(typed))
+        .route("/raw", routing::get(raw))
+        .layer(axum_jwt::layer(counting.clone()).with_extract(Query::default().with_strip(true)))
+        .with_state(counting.clone());
+
+    let encoded = token();
+    for (n, (path, expected)) in [
+        ("/claims", "alice"),
+        ("/token", "alice"),
+        ("/raw", &encoded[..]),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let req = Request::get(format!("{path}?a=1&access_token={encoded}"))
+            .body(Body::empty())
+            .expect("build the request");
+
+        let res = app.clone().oneshot(req).await.expect("call the router");
+        assert_eq!(
+            res.status(),
+            StatusCode::OK,
+            "{path} gets the verified token"
+        );
+        let body = body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("read the body");
+
+        assert_eq!(body, expected.as_bytes(), "the body of {path}");
+        assert_eq!(counting.calls(), n + 1, "{path} verifies the token once");
+    }
+}
+
+#[tokio::test]
+async fn stripped_lenient_query() {
+    async fn query(uri: Uri) -> String {
+        uri.query().unwrap_or_default().to_owned()
+    }
+
+    let app = Router::new().route("/", routing::get(query)).layer(
+        axum_jwt::layer(decoder())
+            .with_extract(Query::default().with_strip(true))
+            .with_optional(Optional::Lenient),
+    );
+
+    let req = Request::get("/?a=1&access_token=invalid")
+        .body(Body::empty())
+        .expect("build the request");
+
+    assert_eq!(
+        text(app, req).await,
+        (StatusCode::OK, "a=1".to_owned()),
+        "the ignored token is stripped",
+    );
+}
+
+#[derive(Clone)]
+struct Now {
+    ready: bool,
+}
+
+impl Now {
+    fn check(token: &str) -> Result<TokenData<Value>, Error> {
+        if token == "alice" {
+            let claims = json!({ "sub": token });
+            Ok(TokenData {
+                header: Header::default(),
+                claims,
+            })
+        } else {
+            Err(Error::Signature)
+        }
+    }
+}
+
+impl Verifier for Now {
+    async fn verify(&self, token: &str) -> Result<TokenData<Value>, Error> {
+        assert!(!self.ready, "the ready verifier isn't awaited");
+        Self::check(token)
+    }
+
+    fn verify_now(&self, token: &str) -> Option<Result<TokenData<Value>, Error>> {
+        self.ready.then(|| Self::check(token))
+    }
+}
+
+#[tokio::test]
+async fn verify_now() {
+    for ready in [true, false] {
+        let verifier = Now { ready };
+        let check = |t: &Token<User>| t.claims.sub == "alice";
+        let app = Router::new()
+            .route("/", routing::get(async || {}))
+            .layer(axum_jwt::layer(verifier).with_filter(check));
+
+        let bearer = |token| {
+            get()
+                .header(header::AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::empty())
+                .expect("build the request")
+        };
+
+        let status = send(app.clone(), bearer("alice")).await;
+        assert_eq!(status, StatusCode::OK, "the valid token, ready: {ready}");
+
+        let status = send(app, bearer("bob")).await;
+        assert_eq!(
+            status,
+            StatusCode::UNAUTHORIZED,
+            "the invalid token, ready: {ready}",
+        );
+    }
+}
+
+#[tokio::test]
+async fn pending_filter() {
+    let check = |t: &Token<User>| {
+        let alice = t.claims.sub == "alice";
+        async move {
+            if alice {
+                Ok(())
+            } else {
+                Err(StatusCode::FORBIDDEN)
+            }
+        }
+    };
+
+    let app = Router::new()
+        .route("/", routing::get(async || {}))
+        .layer(axum_jwt::layer(decoder()).with_async_filter(check));
+
+    let bearer = |token: &str| {
+        get()
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::empty())
+            .expect("build the request")
+    };
+
+    let status = send(app.clone(), bearer(&token())).await;
+    assert_eq!(status, StatusCode::OK, "the filter passes");
+
+    let bob = Encoder::from_key(EncodingKey::from_secret(b"secret"))
+        .encode(&json!({ "sub": "bob" }))
+        .expect("encode the token");
+
+    let status = send(app, bearer(&bob)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "the filter rejects");
+}
+
+#[tokio::test]
+async fn request_filter() {
+    // The `sub` claim must match the `/users/{sub}` path
+    let check = |t: &Token<User>, parts: &Parts| {
+        if parts.uri.path().strip_prefix("/users/") == Some(&t.claims.sub) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    };
+
+    let app = Router::new()
+        .route("/users/{sub}", routing::get(async || {}))
+        .layer(axum_jwt::layer(decoder()).with_request_filter(check));
+
+    let status = send(app.clone(), bearer("/users/alice", &token())).await;
+    assert_eq!(status, StatusCode::OK, "the path matches the claim");
+
+    let status = send(app.clone(), bearer("/users/bob", &token())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "another user");
+
+    let req = Request::get("/users/alice")
+        .body(Body::empty())
+        .expect("build the request");
+
+    let status = send(app, req).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the token is missing");
+}
+
+#[tokio::test]
+async fn custom_render() {
+    fn problem(error: Error<Response>) -> Response {
+        let status = match error {
+            Error::Missing => StatusCode::UNAUTHORIZED,
+            Error::Rejected => StatusCode::FORBIDDEN,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        (status, "problem").into_response()
+    }
+
+    async fn hello(Claims(u): Claims<User>) -> String {
+        u.sub
+    }
+
+    let decoder = decoder().with_render(problem);
+    let extractor = Router::new()
+        .route("/", routing::get(hello))
+        .with_state(decoder.clone());
+
+    let middleware = Router::new()
+        .route("/", routing::get(async || {}))
+        .layer(axum_jwt::layer(decoder).with_filter(|t: &Token<User>| t.claims.sub == "bob"));
+
+    let missing = || get().body(Body::empty()).expect("build the request");
+    let cases = [
+        (extractor.clone(), missing(), StatusCode::UNAUTHORIZED),
+        (extractor, bearer("/", "invalid"), StatusCode::BAD_REQUEST),
+        (middleware.clone(), missing(), StatusCode::UNAUTHORIZED),
+        (
+            middleware.clone(),
+            bearer("/", "invalid"),
+            StatusCode::BAD_REQUEST,
+        ),
+        (middleware, bearer("/", &token()), StatusCode::FORBIDDEN),
+    ];
+
+    for (n, (app, req, status)) in cases.into_iter().enumerate() {
+        let res = app.oneshot(req).await.expect("call the router");
+        assert_eq!(res.status(), status, "the status of the case {n}");
+        assert!(
+            !res.headers().contains_key(header::WWW_AUTHENTICATE),
+            "no challenge in the case {n}",
+        );
+
+        let body = body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("read the body");
+
+        assert_eq!(&body[..], b"problem", "the body of the case {n}");
+    }
+}
+
+#[tokio::test]
+async fn optional_extractor() {
+    async fn hello(claims: Option<Claims<User>>) -> String {
+        claims.map_or_else(|| "anonymous".to_owned(), |Claims(u)| u.sub)
+    }
+
+    for optional in [Optional::Strict, Optional::Lenient] {
+        let app = Router::new()
+            .route("/", routing::get(hello))
+            .with_state(decoder().with_optional(optional));
+
+        let missing = get().body(Body::empty()).expect("build the request");
+        assert_eq!(
+            text(app.clone(), missing).await,
+            (StatusCode::OK, "anonymous".to_owned()),
+            "no token, {optional:?}",
+        );
+
+        assert_eq!(
+            text(app.clone(), bearer("/", &token())).await,
+            (StatusCode::OK, "alice".to_owned()),
+            "the valid token, {optional:?}",
+        );
+
+        let (status, body) = text(app, bearer("/", "invalid")).await;
+        match optional {
+            Optional::Strict => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "the invalid token");
+            }
+            Optional::Lenient => {
+                assert_eq!(
+                    (status, &body[..]),
+                    (StatusCode::OK, "anonymous"),
+                    "the invalid token is ignored",
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn optional_layer() {
+    async fn hello(t: Option<Extension<Token<User>>>) -> String {
+        t.map_or_else(|| "anonymous".to_owned(), |Extension(t)| t.claims.sub)
+    }
+
+    let check = |t: &Token<User>| {
+        if t.claims.sub == "alice" {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    };
+
+    for optional in [Optional::Strict, Optional::Lenient] {
+        let app = Router::new().route("/", routing::get(hello)).layer(
+            axum_jwt::layer(decoder())
+                .with_filter(check)
+                .store_to_extension()
+                .with_optional(optional),
+        );
+
+        let missing = get().body(Body::empty()).expect("build the request");
+        assert_eq!(
+            text(app.clone(), missing).await,
+            (StatusCode::OK, "anonymous".to_owned()),
+            "no token is stored, {optional:?}",
+        );
+
+        assert_eq!(
+            text(app.clone(), bearer("/", &token())).await,
+            (StatusCode::OK, "alice".to_owned()),
+            "the token is stored, {optional:?}",
+        );
+
+        assert_eq!(
+            send(app.clone(), bearer("/", &encode("bob"))).await,
+            StatusCode::FORBIDDEN,
+            "the filter rejects, {optional:?}",
+        );
+
+        let (status, body) = text(app, bearer("/", "invalid")).await;
+        match optional {
+            Optional::Strict => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "the invalid token");
+            }
+            Optional::Lenient => {
+                assert_eq!(
+                    (status, &body[..]),
+                    (StatusCode::OK, "anonymous"),
+                    "the invalid token isn't stored",
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn store_with() {
+    #[derive(Clone)]
+    struct CurrentUser {
+        id: String,
+    }
+
+    async fn hello(Extension(u): Extension<CurrentUser>, Extension(s): Extension<&str>) -> String {
+        format!("{} {s}", u.id)
+    }
+
+    let app = Router::new().route("/", routing::get(hello)).layer(
+        axum_jwt::layer(decoder())
+            .with_filter(|t: &Token<User>| !t.claims.sub.is_empty())
+            .store_with::<User, _, _>(|t| CurrentUser {
+                id: t.claims.sub.clone(),
+            })
+            .store_with(|_: &Token<User>| "first")
+            .store_with(|_: &Token<User>| "last"),
+    );
+
+    assert_eq!(
+        text(app.clone(), bearer("/", &token())).await,
+        (StatusCode::OK, "alice last".to_owned()),
+        "the values are stored, the last one wins on the same type",
+    );
+
+    assert_eq!(
+        send(app, bearer("/", "invalid")).await,
+        StatusCode::UNAUTHORIZED,
+        "nothing is stored for an invalid token",
+    );
+}
+
+#[tokio::test]
+async fn store_before_filter() {
+    async fn hello(Extension(t): Extension<Token<User>>) -> String {
+        t.claims.sub
+    }
+
+    let app = Router::new().route("/", routing::get(hello)).layer(
+        axum_jwt::layer(decoder())
+            .store_to_extension()
+            .with_filter(|t: &Token<User>| t.claims.sub == "alice"),
+    );
+
+    assert_eq!(
+        text(app, bearer("/", &token())).await,
+        (StatusCode::OK, "alice".to_owned()),
+        "the token is stored with the claims type of the filter",
+    );
+}
+
+#[tokio::test]
+async fn store_with_before_filter() {
+    async fn hello(Extension(sub): Extension<String>) -> String {
+        sub
+    }
+
+    let app = Router::new().route("/", routing::get(hello)).layer(
+        axum_jwt::layer(decoder())
+            .store_with::<User, _, _>(|t| t.claims.sub.clone())
+            .with_filter(|t: &Token<User>| t.claims.sub == "alice"),
+    );
+
+    assert_eq!(
+        text(app.clone(), bearer("/", &token())).await,
+        (StatusCode::OK, "alice".to_owned()),
+        "the value is stored with the claims type of the filter",
+    );
+
+    assert_eq!(
+        send(app, bearer("/", &encode("bob"))).await,
+        StatusCode::UNAUTHORIZED,
+        "the filter rejects",
+    );
+}
+
+#[tokio::test]
+async fn composed_filters() {
+    let first = Arc::new(AtomicUsize::new(0));
+    let app = Router::new().route("/", routing::get(async || {})).layer(
+        axum_jwt::layer(decoder())
+            .with_filter({
+                let first = first.clone();
+                move |_: &Token<User>| {
+                    first.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+            })
+            .with_filter(|t| {
+                if t.claims.sub == "alice" {
+                    Ok(())
+                } else {
+                    Err(StatusCode::FORBIDDEN)
+                }
+            }),
+    );
+
+    let status = send(app.clone(), bearer("/", &token())).await;
+    assert_eq!(status, StatusCode::OK, "both filters pass");
+
+    let status = send(app, bearer("/", &encode("bob"))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "the second filter rejects");
+    assert_eq!(
+        first.load(Ordering::Relaxed),
+        2,
+        "the first filter is called for both tokens",
+    );
+}
+
+#[tokio::test]
+async fn sync_and_async_filters() {
+    let app = Router::new().route("/", routing::get(async || {})).layer(
+        axum_jwt::layer(decoder())
+            .with_filter(|t: &Token<User>| t.claims.sub != "bob")
+            .with_async_filter(|t| {
+                let sub = t.claims.sub.clone();
+                async move {
+                    tokio::task::yield_now().await;
+                    if sub == "alice" {
+                        Ok(())
+                    } else {
+                        Err(StatusCode::FORBIDDEN)
+                    }
+                }
+            }),
+    );
+
+    let status = send(app.clone(), bearer("/", &token())).await;
+    assert_eq!(status, StatusCode::OK, "both filters pass");
+
+    let status = send(app.clone(), bearer("/", &encode("bob"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "the sync filter rejects");
+
+    let status = send(app, bearer("/", &encode("carol"))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "the async filter rejects");
+}
+
+#[tokio::test]
+async fn csrf() {
+    let app = Router::new()
+        .route("/", routing::get(async || {}).post(async || {}))
+        .layer(
+            axum_jwt::layer(decoder())
+                .with_extract(Cookie::default())
+                .with_csrf(Csrf::cookie("csrf")),
+        );
+
+    let req = |method, csrf: Option<&str>| {
+        let mut req = Request::builder().method(method).header(
+            header::COOKIE,
+            format!("access_token={}; csrf=abc", token()),
+        );
+
+        if let Some(csrf) = csrf {
+            req = req.header("x-csrf-token", csrf);
+        }
+
+        req.body(Body::empty()).expect("build the request")
+    };
+
+    let status = send(app.clone(), req("GET", None)).await;
+    assert_eq!(status, StatusCode::OK, "safe methods aren't checked");
+
+    let status = send(app.clone(), req("POST", Some("abc"))).await;
+    assert_eq!(status, StatusCode::OK, "the header matches the cookie");
+
+    for csrf in [None, Some("abd"), Some("ab"), Some("")] {
+        let status = send(app.clone(), req("POST", csrf)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "the header {csrf:?}");
+    }
+}
@@ -0,0 +1,293 @@
+use {
+    axum::{
+        Router,
+        body::{self, Body},
+        http::{Request, StatusCode, header},
+    },
+    axum_jwt::{
+        Decoder, Encoder, Error,
+        jsonwebtoken::{self, DecodingKey, EncodingKey, Header, Validation},
+        refresh::{Pair, Refresh, RefreshError, Store},
+        revoke::{Denylist, Registered},
+    },
+    serde::Serialize,
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+    },
+    tower::ServiceExt,
+};
+
+#[derive(Default)]
+struct Memory {
+    families: Mutex<HashMap<String, HashSet<String>>>,
+    fail: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+struct Unavailable;
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the storage is unavailable")
+    }
+}
+
+impl Store for Memory {
+    type Error = Unavailable;
+
+    async fn issue(&self, family: &str, jti: &str, _: u64) -> Result<(), Self::Error> {
+        if self.fail.load(Ordering::Relaxed) {
+            return Err(Unavailable);
+        }
+
+        let mut families = self.families.lock().expect("lock families");
+        families
+            .entry(family.to_owned())
+            .or_default()
+            .insert(jti.to_owned());
+
+        Ok(())
+    }
+
+    async fn consume(&self, family: &str, jti: &str) -> Result<bool, Self::Error> {
+        let mut families = self.families.lock().expect("lock families");
+        Ok(families
+            .get_mut(family)
+            .is_some_and(|jtis| jtis.remove(jti)))
+    }
+
+    async fn revoke(&self, family: &str) -> Result<(), Self::Error> {
+        self.families.lock().expect("lock families").remove(family);
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct User {
+    sub: &'static str,
+}
+
+fn validation() -> Validation {
+    let mut validation = Validation::default();
+    validation.set_audience(&["refresh"]);
+    validation
+}
+
+fn decoder() -> Decoder {
+    Decoder::new(DecodingKey::from_secret(b"refresh"), validation())
+}
+
+fn refresh_with(decoder: Decoder, store: Memory) -> Refresh<Memory> {
+    let access = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+    let header = Header {
+        typ: Some("refresh+jwt".to_owned()),
+        ..Header::default()
+    };
+
+    let refresh =
+        Encoder::new(EncodingKey::from_secret(b"refresh"), header).with_audience(["refresh"]);
+
+    Refresh::new(access, refresh, decoder, store)
+}
+
+fn refresh(store: Memory) -> Refresh<Memory> {
+    refresh_with(decoder(), store)
+}
+
+#[tokio::test]
+async fn rotate() {
+    let refresh = refresh(Memory::default());
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+    let next = refresh.refresh(&pair.refresh_token).await.expect("refresh");
+    assert_ne!(
+        pair.refresh_token, next.refresh_token,
+        "a new token is issued"
+    );
+    assert!(
+        refresh.refresh(&next.refresh_token).await.is_ok(),
+        "the new token can be used",
+    );
+}
+
+#[tokio::test]
+async fn detect_reuse() {
+    let refresh = refresh(Memory::default());
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+    let next = refresh.refresh(&pair.refresh_token).await.expect("refresh");
+
+    assert!(
+        matches!(
+            refresh.refresh(&pair.refresh_token).await,
+            Err(RefreshError::Reused),
+        ),
+        "the token is used again",
+    );
+
+    assert!(
+        matches!(
+            refresh.refresh(&next.refresh_token).await,
+            Err(RefreshError::Reused),
+        ),
+        "the whole family is revoked",
+    );
+}
+
+#[tokio::test]
+async fn reject_access_token() {
+    let refresh = refresh(Memory::default());
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+    assert!(
+        matches!(
+            refresh.refresh(&pair.access_token).await,
+            Err(RefreshError::Token(_)),
+        ),
+        "the access token isn't a refresh token",
+    );
+}
+
+#[tokio::test]
+async fn retry_after_failure() {
+    let fail = Arc::new(AtomicBool::new(false));
+    let refresh = refresh(Memory {
+        fail: fail.clone(),
+        ..Memory::default()
+    });
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+
+    fail.store(true, Ordering::Relaxed);
+    assert!(
+        matches!(
+            refresh.refresh(&pair.refresh_token).await,
+            Err(RefreshError::Store(Unavailable)),
+        ),
+        "the storage is unavailable",
+    );
+
+    fail.store(false, Ordering::Relaxed);
+    assert!(
+        refresh.refresh(&pair.refresh_token).await.is_ok(),
+        "the token isn't consumed by the failed attempt",
+    );
+}
+
+#[tokio::test]
+async fn token_endpoint() {
+    let refresh = refresh(Memory::default());
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+    let app = Router::new().route_service("/token", refresh);
+
+    let post = |token: &str| {
+        Request::post("/token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!(r#"{{"refresh_token":"{token}"}}"#)))
+            .expect("build the request")
+    };
+
+    let res = app
+        .clone()
+        .oneshot(post(&pair.refresh_token))
+        .await
+        .expect("call the endpoint");
+
+    assert_eq!(res.status(), StatusCode::OK, "the token is rotated");
+    assert_eq!(res.headers()[header::CACHE_CONTROL], "no-store", "no cache");
+    let body = body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("read the body");
+
+    let next: Pair = serde_json::from_slice(&body).expect("the pair");
+    assert_eq!(next.token_type, "Bearer", "the token type");
+
+    let res = app
+        .oneshot(post(&pair.refresh_token))
+        .await
+        .expect("call the endpoint");
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST, "the token is reused");
+    let body = body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("read the body");
+
+    assert_eq!(&body[..], br#"{"error":"invalid_grant"}"#, "the error");
+}
+
+#[tokio::test]
+async fn reject_revoked() {
+    let denylist = Denylist::new();
+    let refresh = refresh_with(
+        decoder().with_revocation(denylist.clone()),
+        Memory::default(),
+    );
+
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+    let key = DecodingKey::from_secret(b"refresh");
+    let claims = jsonwebtoken::decode::<Registered>(&pair.refresh_token, &key, &validation())
+        .expect("decode the refresh token")
+        .claims;
+
+    denylist.revoke(&claims);
+    let res = refresh.refresh(&pair.refresh_token).await;
+    assert!(
+        matches!(res, Err(RefreshError::Token(Error::Revoked))),
+        "the refresh token is revoked",
+    );
+
+    let e = res.expect_err("the error");
+    assert_eq!(
+        e.to_string(),
+        "invalid refresh token: the token has been revoked",
+        "the error message",
+    );
+}
+
+#[tokio::test]
+async fn form_request() {
+    let refresh = refresh(Memory::default());
+    let pair = refresh.issue(&User { sub: "alice" }).await.expect("issue");
+    let app = Router::new().route_service("/token", refresh);
+
+    let post = |body: String| {
+        Request::post("/token")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("build the request")
+    };
+
+    let error = async |req: Request<Body>| {
+        let res = app.clone().oneshot(req).await.expect("call the endpoint");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "the request fails");
+        body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("read the body")
+    };
+
+    let token = &pair.refresh_token;
+    let body = error(post(format!("grant_type=password&refresh_token={token}"))).await;
+    assert_eq!(
+        &body[..],
+        br#"{"error":"unsupported_grant_type"}"#,
+        "another grant",
+    );
+
+    let body = error(post(format!("refresh_token={token}"))).await;
+    assert_eq!(
+        &body[..],
+        br#"{"error":"invalid_request"}"#,
+        "no grant type",
+    );
+
+    let res = app
+        .clone()
+        .oneshot(post(format!(
+            "grant_type=refresh_token&refresh_token={token}&scope=read"
+        )))
+        .await
+        .expect("call the endpoint");
+
+    assert_eq!(res.status(), StatusCode::OK, "the token is rotated");
+}
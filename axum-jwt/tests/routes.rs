@@ -0,0 +1,190 @@
+#![cfg(feature = "routes")]
+
+use {
+    axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode, header},
+        response::Response,
+        routing,
+    },
+    axum_jwt::{
+        Decoder, Encoder, Optional, authz,
+        jsonwebtoken::{DecodingKey, EncodingKey},
+        layer::JwtLayer,
+        routes::{Access, Routes},
+    },
+    serde_json::json,
+    tower::ServiceExt,
+};
+
+fn token(scope: &str) -> String {
+    let encoder = Encoder::from_key(EncodingKey::from_secret(b"secret"));
+    encoder
+        .encode(&json!({ "scope": scope }))
+        .expect("encode the token")
+}
+
+fn router(routes: Routes) -> Router {
+    let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+    endpoints(axum_jwt::layer(decoder).with_routes(routes))
+}
+
+fn endpoints(layer: JwtLayer) -> Router {
+    let orders = Router::new().route("/{id}", routing::get(async || {}));
+    Router::new()
+        .route("/health", routing::get(async || {}))
+        .route("/me", routing::get(async || {}))
+        .route("/orders/{id}", routing::get(async || {}))
+        .route("/docs/{*path}", routing::get(async || {}))
+        .nest("/api/orders", orders)
+        .layer(layer)
+}
+
+async fn get(app: &Router, path: &str, token: Option<&str>) -> Response {
+    let mut req = Request::get(path);
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+
+    let req = req.body(Body::empty()).expect("build the request");
+    app.clone().oneshot(req).await.expect("call the router")
+}
+
+fn routes() -> Routes {
+    Routes::new()
+        .public("/health")
+        .public("/docs/{*path}")
+        .require("/orders/{id}", authz::require_scopes(["read:orders"]))
+        .require("/api/orders/{id}", authz::require_scopes(["read:orders"]))
+}
+
+#[tokio::test]
+async fn public_route() {
+    let app = router(routes());
+    let res = get(&app, "/health", None).await;
+    assert_eq!(res.status(), StatusCode::OK, "no token is needed");
+
+    let res = get(&app, "/docs/a/b", None).await;
+    assert_eq!(res.status(), StatusCode::OK, "the wildcard route");
+}
+
+#[tokio::test]
+async fn authenticated_route() {
+    let app = router(routes());
+    let res = get(&app, "/me", None).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "no token");
+    assert_eq!(
+        res.headers()[header::WWW_AUTHENTICATE],
+        "Bearer",
+        "the challenge without an error code",
+    );
+
+    let res = get(&app, "/me", Some(&token(""))).await;
+    assert_eq!(res.status(), StatusCode::OK, "any valid token");
+}
+
+#[tokio::test]
+async fn require_route() {
+    let app = router(routes());
+    for path in ["/orders/1", "/api/orders/1"] {
+        let res = get(&app, path, Some(&token("write:orders"))).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "no scope for {path}");
+        let challenge = res.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .expect("the challenge");
+
+        assert!(
+            challenge.contains(r#"error="insufficient_scope""#),
+            "the error code for {path}",
+        );
+
+        let res = get(&app, path, Some(&token("read:orders"))).await;
+        assert_eq!(res.status(), StatusCode::OK, "the scope for {path}");
+    }
+}
+
+#[tokio::test]
+async fn match_route_not_path() {
+    let app = router(Routes::new().public("/orders/{id}"));
+    let res = get(&app, "/orders/1", None).await;
+    assert_eq!(res.status(), StatusCode::OK, "the route path is matched");
+
+    let app = router(Routes::new().public("/orders/1"));
+    let res = get(&app, "/orders/1", None).await;
+    assert_eq!(
+        res.status(),
+        StatusCode::UNAUTHORIZED,
+        "the request path isn't matched",
+    );
+}
+
+#[tokio::test]
+async fn fallback() {
+    let app = router(routes());
+    let res = get(&app, "/unknown", None).await;
+    assert_eq!(
+        res.status(),
+        StatusCode::UNAUTHORIZED,
+        "the router fallback requires a token",
+    );
+
+    let app = router(routes().with_fallback(Access::Public));
+    let res = get(&app, "/unknown", None).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND, "the public fallback");
+
+    let res = get(&app, "/me", None).await;
+    assert_eq!(res.status(), StatusCode::OK, "not in the table");
+}
+
+#[tokio::test]
+async fn optional_route() {
+    let routes = Routes::new()
+        .optional("/health")
+        .require("/orders/{id}", authz::require_scopes(["read:orders"]));
+
+    for optional in [Optional::Strict, Optional::Lenient] {
+        let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
+        let layer = axum_jwt::layer(decoder)
+            .with_routes(routes.clone())
+            .with_optional(optional);
+
+        let app = endpoints(layer);
+        let res = get(&app, "/health", None).await;
+        assert_eq!(res.status(), StatusCode::OK, "anonymous in {optional:?}");
+
+        let res = get(&app, "/health", Some("garbage")).await;
+        let status = match optional {
+            Optional::Strict => StatusCode::UNAUTHORIZED,
+            Optional::Lenient => StatusCode::OK,
+        };
+
+        assert_eq!(res.status(), status, "invalid token in {optional:?}");
+
+        for path in ["/me", "/orders/1"] {
+            let res = get(&app, path, None).await;
+            assert_eq!(
+                res.status(),
+                StatusCode::UNAUTHORIZED,
+                "anonymous to {path} in {optional:?}",
+            );
+
+            let res = get(&app, path, Some("garbage")).await;
+            assert_eq!(
+                res.status(),
+                StatusCode::UNAUTHORIZED,
+                "invalid token to {path} in {optional:?}",
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn optional_route_without_mode() {
+    let app = router(Routes::new().optional("/health"));
+    let res = get(&app, "/health", None).await;
+    assert_eq!(res.status(), StatusCode::OK, "anonymous");
+
+    let res = get(&app, "/health", Some("garbage")).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "strict by default");
+}
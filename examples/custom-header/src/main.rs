@@ -1,16 +1,18 @@
 use {
     axum::{Router, http::request::Parts, routing},
-    axum_jwt::{Decoder, Extract, Token, jsonwebtoken::DecodingKey},
+    axum_jwt::{Decoder, Error, Extract, Token, jsonwebtoken::DecodingKey},
     serde::Deserialize,
-    std::io::Result,
+    std::{borrow::Cow, io},
     tokio::net::TcpListener,
 };
 
+#[derive(Default)]
 struct Custom;
 
 impl Extract for Custom {
-    fn extract(parts: &mut Parts) -> Option<&str> {
-        parts.headers.get("X-Auth-Token")?.to_str().ok()
+    fn extract<'p>(&self, parts: &'p mut Parts) -> Result<Cow<'p, str>, Error> {
+        let value = parts.headers.get("X-Auth-Token").ok_or(Error::Missing)?;
+        value.to_str().map(Cow::Borrowed).map_err(|_| Error::Scheme)
     }
 }
 
@@ -25,7 +27,7 @@ async fn hello(Token { claims, .. }: Token<User, Custom>) -> String {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> io::Result<()> {
     let decoder = Decoder::from_key(DecodingKey::from_secret(b"secret"));
 
     let app = Router::new()